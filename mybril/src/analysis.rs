@@ -0,0 +1,210 @@
+use std::{
+    collections::{BTreeMap, BTreeSet, HashMap, HashSet},
+    str::FromStr,
+};
+
+use crate::{
+    dataflow::{constants, Defined, Live},
+    error::Error,
+    ir::{Bril, Function},
+    pass_manager::AnalysisManager,
+    ssa::{self, Cfg, DominanceFrontiers, DominatorTree, Dominators},
+};
+
+/// An analysis printed in the format of bril's `df.py` or `dom.py`, as in `tests/examples`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Report {
+    /// Variables defined at the start and end of each block.
+    Defined,
+    /// Variables live at the start and end of each block.
+    Live,
+    /// Constants known at the start and end of each block.
+    ConstantPropagation,
+    /// The blocks dominating each block.
+    Dominators,
+    /// The children of each block in the dominator tree.
+    DominatorTree,
+    /// The dominance frontier of each block.
+    DominanceFrontiers,
+}
+
+const REPORTS: [(&str, Report); 6] = [
+    ("defined", Report::Defined),
+    ("live", Report::Live),
+    ("cprop", Report::ConstantPropagation),
+    ("dom", Report::Dominators),
+    ("tree", Report::DominatorTree),
+    ("front", Report::DominanceFrontiers),
+];
+
+impl FromStr for Report {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        REPORTS
+            .iter()
+            .find(|(name, _)| *name == s)
+            .map(|(_, report)| *report)
+            .ok_or_else(|| {
+                let names = REPORTS.map(|(name, _)| name);
+                format!(
+                    "unknown analysis `{s}`; expected one of {}",
+                    names.join(", ")
+                )
+            })
+    }
+}
+
+/// Prints `report` for every function of `bril` with instructions.
+pub fn report(bril: &Bril, report: Report) -> Result<String, Error> {
+    let mut output = String::new();
+    for function in bril.functions.iter().filter(|f| !f.instrs.is_empty()) {
+        match report {
+            Report::Defined | Report::Live | Report::ConstantPropagation => {
+                dataflow(function, report, &mut output)?
+            }
+            Report::Dominators | Report::DominatorTree | Report::DominanceFrontiers => {
+                dominance(function, report, &mut output)?
+            }
+        }
+    }
+    Ok(output)
+}
+
+fn dataflow(function: &Function, report: Report, output: &mut String) -> Result<(), Error> {
+    let mut analyses = AnalysisManager::default();
+    let cfg = analyses.get::<Cfg>(function)?;
+    let set = |vars: &HashSet<String>| facts(vars.iter().map(|var| (var, var.clone())));
+    let result = match report {
+        Report::Defined => by_block(&analyses.get::<Defined>(function)?.0, set),
+        Report::Live => by_block(&analyses.get::<Live>(function)?.0, set),
+        // `df.py` knows nothing of the arguments at the entry.
+        _ => by_block(&constants(&cfg, HashMap::new()), |known| {
+            facts(
+                known
+                    .iter()
+                    .map(|(var, constant)| (var, format!("{var}: {constant}"))),
+            )
+        }),
+    };
+    for block in cfg.blocks() {
+        let label = block[0].label().unwrap();
+        let (facts_in, facts_out) = &result[label];
+        output.push_str(&format!("{label}:\n"));
+        output.push_str(&format!("  in:  {facts_in}\n"));
+        output.push_str(&format!("  out: {facts_out}\n"));
+    }
+    Ok(())
+}
+
+fn by_block<S>(
+    result: &HashMap<String, (S, S)>,
+    print: impl Fn(&S) -> String,
+) -> HashMap<String, (String, String)> {
+    result
+        .iter()
+        .map(|(label, (facts_in, facts_out))| (label.clone(), (print(facts_in), print(facts_out))))
+        .collect()
+}
+
+// Sorted by variable and comma separated, or `∅`.
+fn facts<'a>(facts: impl Iterator<Item = (&'a String, String)>) -> String {
+    let facts = facts.collect::<BTreeMap<_, _>>();
+    if facts.is_empty() {
+        return "∅".to_string();
+    }
+    facts.into_values().collect::<Vec<_>>().join(", ")
+}
+
+fn dominance(function: &Function, report: Report, output: &mut String) -> Result<(), Error> {
+    // `dom.py` gives a targeted first block an entry block of its own, which shows up in the output.
+    let mut function = function.clone();
+    ssa::add_entry(&mut function);
+
+    let mut analyses = AnalysisManager::default();
+    // Every block gets a key, even with nothing to list.
+    let mut result = analyses
+        .get::<Cfg>(&function)?
+        .blocks()
+        .map(|block| (block[0].label().unwrap().to_string(), BTreeSet::new()))
+        .collect::<BTreeMap<_, _>>();
+    let sets: HashMap<String, Vec<String>> = match report {
+        Report::Dominators => sets(&analyses.get::<Dominators>(&function)?.0),
+        Report::DominatorTree => analyses.get::<DominatorTree>(&function)?.0.clone(),
+        _ => sets(&analyses.get::<DominanceFrontiers>(&function)?.0),
+    };
+    for (label, set) in sets {
+        result.entry(label).or_default().extend(set);
+    }
+    output.push_str(&serde_json::to_string_pretty(&result)?);
+    output.push('\n');
+    Ok(())
+}
+
+fn sets(map: &HashMap<String, HashSet<String>>) -> HashMap<String, Vec<String>> {
+    map.iter()
+        .map(|(label, set)| (label.clone(), set.iter().cloned().collect()))
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use std::{fs, path::Path};
+
+    use crate::{analysis::Report, parser::parse};
+
+    fn expect(dir: &str, reports: &[(Report, &str)]) {
+        let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join(dir);
+        for entry in fs::read_dir(dir).unwrap() {
+            let path = entry.unwrap().path();
+            if path.extension() != Some("bril".as_ref()) {
+                continue;
+            }
+            let bril = parse(&fs::read_to_string(&path).unwrap()).unwrap();
+            for (report, extension) in reports {
+                let expected = fs::read_to_string(path.with_extension(extension)).unwrap();
+                assert_eq!(
+                    super::report(&bril, *report).unwrap(),
+                    expected,
+                    "{} {extension}",
+                    path.display()
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_dataflow_reports() {
+        expect(
+            "tests/examples/df",
+            &[
+                (Report::Defined, "defined.out"),
+                (Report::Live, "live.out"),
+                (Report::ConstantPropagation, "cprop.out"),
+            ],
+        );
+    }
+
+    #[test]
+    fn test_dominance_reports() {
+        expect(
+            "tests/examples/dom",
+            &[
+                (Report::Dominators, "dom.json"),
+                (Report::DominatorTree, "tree.json"),
+                (Report::DominanceFrontiers, "front.json"),
+            ],
+        );
+    }
+
+    #[test]
+    fn test_unknown_report() {
+        assert_eq!(
+            "avail".parse::<Report>(),
+            Err(
+                "unknown analysis `avail`; expected one of defined, live, cprop, dom, tree, front"
+                    .to_string()
+            )
+        );
+    }
+}
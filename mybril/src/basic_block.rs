@@ -4,15 +4,16 @@ use std::{
     ops::Deref,
 };
 
-use crate::Instruction;
+use crate::ir::{Bril, Extra, Instruction, Op, Type};
 
+/// An instructions chunk with a label at the beginning and a terminator at the end and no label or
+/// terminator in the middle.
 #[derive(Debug, Clone)]
-// An instructions chunk with a label at the beginning and a terminator at the end and no label or terminator in the middle.
 pub struct BasicBlock(pub(crate) Vec<Instruction>);
 
-impl Into<Vec<Instruction>> for BasicBlock {
-    fn into(self) -> Vec<Instruction> {
-        self.0
+impl From<BasicBlock> for Vec<Instruction> {
+    fn from(block: BasicBlock) -> Self {
+        block.0
     }
 }
 
@@ -25,31 +26,47 @@ impl Deref for BasicBlock {
 }
 
 impl BasicBlock {
+    /// The blocks of `instrs` as [`partition`] splits them, each given a label, `b1`, `b2` and so on
+    /// if it has none, and a terminator, a `jmp` to the next block or a `ret` after the last.
     pub fn new_blocks(instrs: &[Instruction]) -> Vec<Self> {
         let mut partitioned = partition(instrs);
         add_label(&mut partitioned);
         add_terminatior(&mut partitioned);
-        partitioned
-            .into_iter()
-            .map(|block| Self(block))
-            .collect::<Vec<_>>()
+        partitioned.into_iter().map(Self).collect::<Vec<_>>()
     }
 
-    pub fn insert_phi(&mut self, phis: &HashMap<String, HashMap<String, String>>) {
+    /// Labels control can leave this block for: the terminator's, and a failing `guard`'s just before it.
+    pub fn successors(&self) -> impl Iterator<Item = &String> {
+        self.iter()
+            .filter(|instr| matches!(instr.op(), Some(Op::Br | Op::Jmp | Op::Guard)))
+            .flat_map(|instr| instr.labels())
+    }
+
+    pub fn insert_phi(
+        &mut self,
+        phis: &HashMap<String, HashMap<String, String>>,
+        types: &HashMap<String, Type>,
+    ) {
+        // Sorted so that the output doesn't depend on hash map order.
+        let mut phis = phis.iter().collect::<Vec<_>>();
+        phis.sort_by_key(|(var_name, _)| *var_name);
+
         self.0 = iter::once(self[0].clone())
             .chain(
                 phis.into_iter()
                     .map(|(var_name, aliases)| {
-                        let aliases = aliases.iter().collect::<Vec<_>>();
-
-                        Instruction {
-                            op: Some("phi".to_string()),
-                            labels: Some(
-                                aliases.iter().map(|(label, _)| label.to_string()).collect(),
-                            ),
-                            dest: Some(var_name.to_string()),
-                            args: Some(aliases.iter().map(|(_, var)| var.to_string()).collect()),
-                            ..Default::default()
+                        let mut aliases = aliases.iter().collect::<Vec<_>>();
+                        aliases.sort();
+
+                        Instruction::Value {
+                            op: Op::Phi,
+                            dest: var_name.to_string(),
+                            r#type: types[var_name].clone(),
+                            args: aliases.iter().map(|(_, var)| var.to_string()).collect(),
+                            funcs: vec![],
+                            labels: aliases.iter().map(|(label, _)| label.to_string()).collect(),
+                            pos: None,
+                            extra: Extra::new(),
                         }
                     })
                     .chain(self[1..].iter().cloned()),
@@ -58,16 +75,16 @@ impl BasicBlock {
     }
 }
 
-struct Labeler {
+pub(crate) struct Labeler {
     banned: HashSet<String>,
     counters: HashMap<String, usize>,
 }
 
 impl Labeler {
-    fn new(partitioned: &[Vec<Instruction>]) -> Self {
+    pub(crate) fn new(partitioned: &[Vec<Instruction>]) -> Self {
         let banned: HashSet<String> = partitioned
             .iter()
-            .filter_map(|block| block[0].label.clone())
+            .filter_map(|block| block[0].label().map(str::to_string))
             .collect();
 
         Self {
@@ -76,8 +93,9 @@ impl Labeler {
         }
     }
 
-    fn label(&mut self, prefix: &str) -> String {
-        let counter = self.counters.entry(prefix.to_string()).or_insert(0);
+    /// A fresh label numbered from 1, as bril's `cfg.py` names blocks, so that analyses print the same names.
+    pub(crate) fn label(&mut self, prefix: &str) -> String {
+        let counter = self.counters.entry(prefix.to_string()).or_insert(1);
         loop {
             let label = format!("{}{}", prefix, counter);
             *counter += 1;
@@ -88,19 +106,22 @@ impl Labeler {
     }
 }
 
+/// Splits `instrs` into basic blocks as they are: a block starts at a label or after a `br`, `jmp` or
+/// `guard`, which it ends with.
 pub fn partition(instrs: &[Instruction]) -> Vec<Vec<Instruction>> {
     let mut blocks = Vec::new();
     let mut block = Vec::new();
     for instr in instrs {
-        if instr.label.is_some() {
+        if instr.label().is_some() {
             if !block.is_empty() {
                 blocks.push(block);
             }
             block = Vec::new();
             block.push(instr.clone());
         } else {
-            match instr.op.as_deref() {
-                Some("br") | Some("jmp") => {
+            match instr.op() {
+                // A failing `guard` jumps to its label, so it also ends a block.
+                Some(Op::Br | Op::Jmp | Op::Guard) => {
                     block.push(instr.clone());
                     blocks.push(block);
                     block = Vec::new();
@@ -118,15 +139,16 @@ pub fn partition(instrs: &[Instruction]) -> Vec<Vec<Instruction>> {
 }
 
 fn add_label(partitioned: &mut [Vec<Instruction>]) {
-    let mut labeler = Labeler::new(&partitioned);
+    let mut labeler = Labeler::new(partitioned);
 
     for block in partitioned {
-        if block[0].label.is_none() {
+        if block[0].label().is_none() {
             block.insert(
                 0,
-                Instruction {
-                    label: Some(labeler.label("b")),
-                    ..Default::default()
+                Instruction::Label {
+                    label: labeler.label("b"),
+                    pos: None,
+                    extra: Extra::new(),
                 },
             );
         }
@@ -135,26 +157,79 @@ fn add_label(partitioned: &mut [Vec<Instruction>]) {
 
 fn add_terminatior(labeled: &mut [Vec<Instruction>]) {
     for i in 0..labeled.len() {
-        let next = labeled
-            .get(i + 1)
-            .map(|block| block[0].label.as_deref().unwrap());
+        let next = labeled.get(i + 1).map(|block| block[0].label().unwrap());
 
-        match labeled[i].last().unwrap().op.as_deref() {
-            Some("br") | Some("jmp") | Some("ret") => {}
+        match labeled[i].last().unwrap().op() {
+            Some(Op::Br | Op::Jmp | Op::Ret) => {}
             _ => {
                 if let Some(next) = next {
-                    labeled[i].push(Instruction {
-                        op: Some("jmp".to_string()),
-                        labels: Some(vec![next.to_string()]),
-                        ..Default::default()
+                    labeled[i].push(Instruction::Effect {
+                        op: Op::Jmp,
+                        args: vec![],
+                        funcs: vec![],
+                        labels: vec![next.to_string()],
+                        pos: None,
+                        extra: Extra::new(),
                     })
                 } else {
-                    labeled[i].push(Instruction {
-                        op: Some("ret".to_string()),
-                        ..Default::default()
+                    labeled[i].push(Instruction::Effect {
+                        op: Op::Ret,
+                        args: vec![],
+                        funcs: vec![],
+                        labels: vec![],
+                        pos: None,
+                        extra: Extra::new(),
                     })
                 }
             }
         }
     }
 }
+
+/// The control flow graph of each function of `bril` in Graphviz's `dot` format.
+pub fn dot(bril: &Bril) -> String {
+    use std::fmt::Write;
+
+    let mut dot = String::new();
+
+    for function in &bril.functions {
+        writeln!(dot, "digraph {} {{", function.name).unwrap();
+
+        let basic_blocks = BasicBlock::new_blocks(&function.instrs);
+
+        for block in &basic_blocks {
+            let label = block[0].label().unwrap();
+            writeln!(dot, "  {label};").unwrap();
+        }
+
+        for block in &basic_blocks {
+            let from = block[0].label().unwrap();
+            for to in block.successors() {
+                writeln!(dot, "  {} -> {};", from, to).unwrap();
+            }
+        }
+
+        writeln!(dot, "}}").unwrap();
+    }
+
+    dot
+}
+
+#[cfg(test)]
+mod test {
+    use insta::{assert_snapshot, glob};
+
+    use super::dot;
+    use crate::{ir::Bril, test::bril2json};
+
+    #[test]
+    fn test_dot() {
+        glob!("..", "tests/test/interp/core/*.bril", |path| {
+            let txt = std::fs::read_to_string(path).unwrap();
+            let json = bril2json(&txt);
+            let bril: Bril = serde_json::from_str(&json).unwrap();
+            let dot = dot(&bril);
+            assert_snapshot!(dot);
+        });
+    }
+}
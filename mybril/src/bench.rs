@@ -0,0 +1,251 @@
+use std::{fmt::Write, fs, path::Path};
+
+use crate::{
+    bytecode,
+    error::Error,
+    interp::{InterpError, Limits},
+    ir::Bril,
+    parser,
+};
+
+/// The arguments of `@main` given by a `# ARGS:` first line, as in bril's test programs.
+pub fn source_args(txt: &str) -> Vec<String> {
+    txt.lines()
+        .next()
+        .and_then(|line| line.strip_prefix("# ARGS:"))
+        .map(|args| args.split_whitespace().map(str::to_string).collect())
+        .unwrap_or_default()
+}
+
+/// A program to benchmark, with the arguments to run it with.
+pub struct Program {
+    pub name: String,
+    pub bril: Bril,
+    pub args: Vec<String>,
+}
+
+/// The programs at `paths`: `.bril` text files, whose `# ARGS:` line gives their arguments, and
+/// `.json` files, run without arguments. A directory stands for its programs in name order.
+///
+/// The `# ARGS:` of a `@main` without parameters are flags for some other tool, like `lvn.py -c` in
+/// `tests/examples`, and are left out.
+pub fn programs(paths: &[String]) -> Result<Vec<Program>, Error> {
+    let mut files = Vec::new();
+    for path in paths {
+        let path = Path::new(path);
+        if path.is_dir() {
+            let mut entries = fs::read_dir(path)?
+                .map(|entry| entry.map(|entry| entry.path()))
+                .collect::<Result<Vec<_>, _>>()?;
+            entries.retain(|path| {
+                path.extension()
+                    .is_some_and(|ext| ext == "bril" || ext == "json")
+            });
+            entries.sort();
+            files.extend(entries);
+        } else {
+            files.push(path.to_path_buf());
+        }
+    }
+
+    files
+        .into_iter()
+        .map(|path| {
+            let txt = fs::read_to_string(&path)?;
+            let (bril, args) = match path.extension() {
+                Some(ext) if ext == "json" => (Bril::from_json(&txt)?, Vec::new()),
+                _ => (parser::parse(&txt)?, source_args(&txt)),
+            };
+            let takes_args = bril.functions.iter().any(|function| {
+                function.name == "main"
+                    && function.args.as_ref().is_some_and(|args| !args.is_empty())
+            });
+            let args = if takes_args { args } else { Vec::new() };
+            Ok(Program {
+                name: path.display().to_string(),
+                bril,
+                args,
+            })
+        })
+        .collect()
+}
+
+/// What running a program printed, and how many instructions it executed or the error it failed with.
+#[derive(Debug)]
+pub struct Run {
+    pub output: String,
+    pub result: Result<u64, InterpError>,
+}
+
+impl Run {
+    pub fn new(bril: &Bril, args: &[String], limits: Limits) -> Self {
+        let mut out = Vec::new();
+        let result = bytecode::run(bril, args, &mut out, limits);
+        Self {
+            output: String::from_utf8_lossy(&out).into_owned(),
+            result: result.map(|profile| profile.total_dyn_inst),
+        }
+    }
+
+    /// The output followed by the error the run failed with, if any, to compare runs by.
+    pub fn transcript(&self) -> String {
+        match &self.result {
+            Ok(_) => self.output.clone(),
+            Err(e) => format!("{}error: {}\n", self.output, e.message),
+        }
+    }
+}
+
+/// A program run before and after optimizing it.
+#[derive(Debug)]
+pub struct Comparison {
+    pub name: String,
+    pub before: Run,
+    pub after: Run,
+}
+
+impl Comparison {
+    /// Runs `program`, optimizes it with `optimize` and runs it again.
+    pub fn new(
+        program: &Program,
+        optimize: impl FnOnce(&mut Bril) -> Result<(), Error>,
+        limits: Limits,
+    ) -> Result<Self, Error> {
+        let before = Run::new(&program.bril, &program.args, limits);
+        let mut bril = program.bril.clone();
+        optimize(&mut bril)?;
+        let after = Run::new(&bril, &program.args, limits);
+        Ok(Self {
+            name: program.name.clone(),
+            before,
+            after,
+        })
+    }
+
+    /// Whether the optimized program printed the same and failed the same way, if it did.
+    pub fn matches(&self) -> bool {
+        self.before.transcript() == self.after.transcript()
+    }
+
+    /// How many times fewer instructions the optimized program executed, when both ran to the end.
+    pub fn speedup(&self) -> Option<f64> {
+        match (&self.before.result, &self.after.result) {
+            (Ok(before), Ok(after)) if *after > 0 => Some(*before as f64 / *after as f64),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Table {
+    Csv,
+    Markdown,
+}
+
+/// The dynamic instruction counts and speedup of each comparison, with `-` for a failed run.
+pub fn table(comparisons: &[Comparison], table: Table) -> String {
+    let header = ["program", "before", "after", "speedup", "output"];
+    let rows = comparisons.iter().map(|comparison| {
+        let count = |run: &Run| match run.result {
+            Ok(count) => count.to_string(),
+            Err(_) => "-".to_string(),
+        };
+        [
+            comparison.name.clone(),
+            count(&comparison.before),
+            count(&comparison.after),
+            comparison
+                .speedup()
+                .map_or("-".to_string(), |speedup| format!("{speedup:.3}")),
+            if comparison.matches() {
+                "same"
+            } else {
+                "changed"
+            }
+            .to_string(),
+        ]
+    });
+
+    let mut output = String::new();
+    match table {
+        Table::Csv => {
+            writeln!(output, "{}", header.join(",")).unwrap();
+            for row in rows {
+                let row = row.map(|cell| csv(&cell));
+                writeln!(output, "{}", row.join(",")).unwrap();
+            }
+        }
+        Table::Markdown => {
+            writeln!(output, "| {} |", header.join(" | ")).unwrap();
+            writeln!(output, "|---|---:|---:|---:|---|").unwrap();
+            for row in rows {
+                writeln!(output, "| {} |", row.join(" | ")).unwrap();
+            }
+        }
+    }
+    output
+}
+
+// Quoted when it has a comma or quote in it.
+fn csv(cell: &str) -> String {
+    if cell.contains([',', '"', '\n']) {
+        format!("\"{}\"", cell.replace('"', "\"\""))
+    } else {
+        cell.to_string()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{table, Comparison, Program, Table};
+    use crate::{interp::Limits, parser::parse};
+
+    #[test]
+    fn test_table() {
+        let txt = "@main(n: int) {\n  one: int = const 1;\n  two: int = const 1;\n  x: int = add n one;\n  print x;\n}\n";
+        let program = Program {
+            name: "a,b.bril".to_string(),
+            bril: parse(txt).unwrap(),
+            args: vec!["4".to_string()],
+        };
+
+        let dropped = Comparison::new(
+            &program,
+            |bril| {
+                bril.functions[0].instrs.remove(1);
+                Ok(())
+            },
+            Limits::default(),
+        )
+        .unwrap();
+        assert_eq!(dropped.before.output, "5\n");
+        assert!(dropped.matches());
+
+        let broken = Comparison::new(
+            &program,
+            |bril| {
+                bril.functions[0].instrs.remove(0);
+                Ok(())
+            },
+            Limits::default(),
+        )
+        .unwrap();
+        assert!(!broken.matches());
+        assert_eq!(broken.speedup(), None);
+
+        let comparisons = [dropped, broken];
+        assert_eq!(
+            table(&comparisons, Table::Csv),
+            "program,before,after,speedup,output\n\"a,b.bril\",4,3,1.333,same\n\"a,b.bril\",4,-,-,changed\n"
+        );
+        assert_eq!(
+            table(&comparisons, Table::Markdown),
+            "\
+| program | before | after | speedup | output |
+|---|---:|---:|---:|---|
+| a,b.bril | 4 | 3 | 1.333 | same |
+| a,b.bril | 4 | - | - | changed |
+"
+        );
+    }
+}
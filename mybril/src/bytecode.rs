@@ -0,0 +1,861 @@
+use std::{collections::HashMap, io::Write};
+
+use crate::{
+    interp::{
+        arity, compare_chars, find, label, main_arguments, returned, Heap, InterpError, Limit,
+        Limits, Pointer, Profile, Value,
+    },
+    ir::{Bril, Function, Instruction, Op, Type},
+};
+
+// `interp`, compiled ahead of time: variables become slots of a vector, labels become instruction
+// indices and callees become function indices, so running a program looks up no names at all.
+// Output, instruction counts and errors are the same as `interp`'s.
+
+type Slot = u32;
+
+// Results nobody reads, e.g. of a value operation without a destination, go here.
+const DISCARD: Slot = 0;
+
+// The instruction a jump lands on, or the error taking it raises.
+type Target = Result<usize, String>;
+
+// One per instruction of the function, labels included, so that errors can point back at it.
+enum Code<'a> {
+    Label(u32),
+    Const {
+        dest: Slot,
+        value: Value,
+    },
+    Id {
+        dest: Slot,
+        arg: Slot,
+    },
+    Unary {
+        op: Op,
+        dest: Slot,
+        arg: Slot,
+    },
+    Binary {
+        op: Op,
+        dest: Slot,
+        lhs: Slot,
+        rhs: Slot,
+    },
+    Jmp(usize),
+    Br {
+        cond: Slot,
+        targets: Box<[Target; 2]>,
+    },
+    Ret(Option<Slot>),
+    Print(Box<[Slot]>),
+    Nop,
+    Call {
+        callee: usize,
+        args: Box<[Slot]>,
+        dest: Slot,
+        instr: &'a Instruction,
+    },
+    Phi {
+        dest: Slot,
+        sources: Box<[(u32, Slot)]>,
+    },
+    Alloc {
+        dest: Slot,
+        size: Slot,
+        instr: &'a Instruction,
+    },
+    Free(Slot),
+    Store {
+        pointer: Slot,
+        value: Slot,
+    },
+    Load {
+        dest: Slot,
+        pointer: Slot,
+    },
+    Ptradd {
+        dest: Slot,
+        pointer: Slot,
+        offset: Slot,
+    },
+    Speculate,
+    Commit,
+    Guard {
+        cond: Slot,
+        target: Box<Target>,
+    },
+    // An instruction that fails whenever it runs, e.g. for having the wrong number of arguments.
+    Fail {
+        op: Op,
+        message: String,
+    },
+}
+
+struct Compiled<'a> {
+    function: &'a Function,
+    code: Vec<Code<'a>>,
+    // Variable names by slot, for error messages.
+    names: Vec<&'a str>,
+    params: Vec<Slot>,
+}
+
+impl Compiled<'_> {
+    fn get(&self, env: &[Option<Value>], slot: Slot) -> Result<Value, InterpError> {
+        env[slot as usize].ok_or_else(|| self.undefined(slot))
+    }
+
+    fn undefined(&self, slot: Slot) -> InterpError {
+        InterpError::new(format!("undefined variable {}", self.names[slot as usize]))
+    }
+}
+
+/// A program compiled for fast interpretation. Compile once, then [`Program::run`] it any number of times.
+pub struct Program<'a> {
+    bril: &'a Bril,
+    functions: Vec<Compiled<'a>>,
+}
+
+impl<'a> Program<'a> {
+    pub fn compile(bril: &'a Bril) -> Self {
+        let functions = bril
+            .functions
+            .iter()
+            .map(|function| Compiler::new(bril, function).compile())
+            .collect();
+        Self { bril, functions }
+    }
+
+    /// Runs `@main` like [`crate::interp::run_limited`].
+    pub fn run<W: Write>(
+        &self,
+        args: &[String],
+        out: W,
+        limits: Limits,
+    ) -> Result<Profile, InterpError> {
+        let main = find(self.bril, "main")?;
+        let compiled = &self.functions[main];
+        let arguments = main_arguments(compiled.function, args)?;
+
+        let mut env = vec![None; compiled.names.len()];
+        for (param, &slot) in compiled
+            .function
+            .args
+            .iter()
+            .flatten()
+            .zip(&compiled.params)
+        {
+            if let Some(value) = arguments.get(param.name.as_str()) {
+                env[slot as usize] = Some(*value);
+            }
+        }
+
+        let mut machine = Machine {
+            program: self,
+            out,
+            heap: Heap::default(),
+            icount: 0,
+            max_instructions: limits.instructions.unwrap_or(u64::MAX),
+            depth: 0,
+            limits,
+        };
+        machine.invoke(main, env)?;
+        machine.heap.leaks()?;
+
+        Ok(Profile {
+            total_dyn_inst: machine.icount,
+            ..Profile::default()
+        })
+    }
+}
+
+/// Compiles `bril` and runs it once, like [`crate::interp::run_limited`] but faster.
+pub fn run<W: Write>(
+    bril: &Bril,
+    args: &[String],
+    out: W,
+    limits: Limits,
+) -> Result<Profile, InterpError> {
+    Program::compile(bril).run(args, out, limits)
+}
+
+struct Compiler<'a> {
+    bril: &'a Bril,
+    function: &'a Function,
+    slots: HashMap<&'a str, Slot>,
+    names: Vec<&'a str>,
+    label_ids: HashMap<&'a str, u32>,
+    // The index of the first instruction carrying each label.
+    targets: HashMap<&'a str, usize>,
+}
+
+impl<'a> Compiler<'a> {
+    fn new(bril: &'a Bril, function: &'a Function) -> Self {
+        let mut targets = HashMap::new();
+        for (index, instr) in function.instrs.iter().enumerate() {
+            if let Some(label) = instr.label() {
+                targets.entry(label).or_insert(index);
+            }
+        }
+        Self {
+            bril,
+            function,
+            slots: HashMap::new(),
+            names: vec![""],
+            label_ids: HashMap::new(),
+            targets,
+        }
+    }
+
+    fn compile(mut self) -> Compiled<'a> {
+        let function = self.function;
+        let params = function
+            .args
+            .iter()
+            .flatten()
+            .map(|param| self.slot(&param.name))
+            .collect();
+        let code = function
+            .instrs
+            .iter()
+            .map(|instr| self.instruction(instr))
+            .collect();
+        Compiled {
+            function,
+            code,
+            names: self.names,
+            params,
+        }
+    }
+
+    fn slot(&mut self, name: &'a str) -> Slot {
+        let names = &mut self.names;
+        *self.slots.entry(name).or_insert_with(|| {
+            names.push(name);
+            (names.len() - 1) as Slot
+        })
+    }
+
+    fn label_id(&mut self, label: &'a str) -> u32 {
+        let next = self.label_ids.len() as u32;
+        *self.label_ids.entry(label).or_insert(next)
+    }
+
+    fn target(&self, instr: &Instruction, index: usize) -> Target {
+        let label = label(instr, index).map_err(|e| e.message)?;
+        match self.targets.get(label) {
+            Some(&target) => Ok(target),
+            None => Err(format!("label {label} not found")),
+        }
+    }
+
+    fn instruction(&mut self, instr: &'a Instruction) -> Code<'a> {
+        let (op, dest) = match instr {
+            Instruction::Label { label, .. } => return Code::Label(self.label_id(label)),
+            Instruction::Constant { dest, value, .. } => {
+                return Code::Const {
+                    dest: self.slot(dest),
+                    value: value.into(),
+                }
+            }
+            Instruction::Value { op, dest, .. } => (*op, self.slot(dest)),
+            Instruction::Effect { op, .. } => (*op, DISCARD),
+        };
+
+        let names = instr.args();
+        if let Some(count) = arity(op) {
+            if names.len() != count {
+                let message = format!("{op} takes {count} argument(s); got {}", names.len());
+                return Code::Fail { op, message };
+            }
+        }
+        let args = names.iter().map(|name| self.slot(name)).collect::<Vec<_>>();
+        let fail = |message: String| Code::Fail { op, message };
+
+        match op {
+            Op::Id => Code::Id { dest, arg: args[0] },
+            Op::Not | Op::Char2int | Op::Int2char => Code::Unary {
+                op,
+                dest,
+                arg: args[0],
+            },
+            Op::Add
+            | Op::Mul
+            | Op::Sub
+            | Op::Div
+            | Op::Eq
+            | Op::Lt
+            | Op::Gt
+            | Op::Le
+            | Op::Ge
+            | Op::And
+            | Op::Or
+            | Op::Fadd
+            | Op::Fmul
+            | Op::Fsub
+            | Op::Fdiv
+            | Op::Feq
+            | Op::Flt
+            | Op::Fle
+            | Op::Fgt
+            | Op::Fge
+            | Op::Ceq
+            | Op::Clt
+            | Op::Cle
+            | Op::Cgt
+            | Op::Cge => Code::Binary {
+                op,
+                dest,
+                lhs: args[0],
+                rhs: args[1],
+            },
+            Op::Print => Code::Print(args.into()),
+            Op::Jmp => match self.target(instr, 0) {
+                Ok(target) => Code::Jmp(target),
+                Err(message) => fail(message),
+            },
+            Op::Br => Code::Br {
+                cond: args[0],
+                targets: Box::new([self.target(instr, 0), self.target(instr, 1)]),
+            },
+            Op::Ret => match args[..] {
+                [] => Code::Ret(None),
+                [arg] => Code::Ret(Some(arg)),
+                _ => fail(format!("ret takes 0 or 1 argument(s); got {}", args.len())),
+            },
+            Op::Nop => Code::Nop,
+            Op::Call => {
+                let name = match instr.funcs() {
+                    [] => return fail("missing functions; expected at least 1".to_string()),
+                    funcs => &funcs[0],
+                };
+                let callee = match find(self.bril, name) {
+                    Ok(callee) => callee,
+                    Err(e) => return fail(e.message),
+                };
+                let params = self.bril.functions[callee]
+                    .args
+                    .as_deref()
+                    .unwrap_or_default();
+                if params.len() != args.len() {
+                    return fail(format!(
+                        "function expected {} arguments, got {}",
+                        params.len(),
+                        args.len()
+                    ));
+                }
+                Code::Call {
+                    callee,
+                    args: args.into(),
+                    dest,
+                    instr,
+                }
+            }
+            Op::Phi => {
+                let labels = instr.labels();
+                if labels.len() != args.len() {
+                    return fail("phi node has unequal numbers of labels and args".to_string());
+                }
+                let sources = labels
+                    .iter()
+                    .zip(args)
+                    .map(|(label, arg)| (self.label_id(label), arg))
+                    .collect();
+                Code::Phi { dest, sources }
+            }
+            Op::Alloc => Code::Alloc {
+                dest,
+                size: args[0],
+                instr,
+            },
+            Op::Free => Code::Free(args[0]),
+            Op::Store => Code::Store {
+                pointer: args[0],
+                value: args[1],
+            },
+            Op::Load => Code::Load {
+                dest,
+                pointer: args[0],
+            },
+            Op::Ptradd => Code::Ptradd {
+                dest,
+                pointer: args[0],
+                offset: args[1],
+            },
+            Op::Speculate => Code::Speculate,
+            Op::Commit => Code::Commit,
+            Op::Guard => Code::Guard {
+                cond: args[0],
+                target: Box::new(self.target(instr, 0)),
+            },
+            Op::Const => unreachable!("constants are `Instruction::Constant`"),
+        }
+    }
+}
+
+fn error<T>(message: String) -> Result<T, InterpError> {
+    Err(InterpError::new(message))
+}
+
+fn mismatch<T>(op: Op, index: usize, r#type: &str) -> Result<T, InterpError> {
+    error(format!("{op} argument {index} must be a {type}"))
+}
+
+fn int(op: Op, index: usize, value: Value) -> Result<i64, InterpError> {
+    match value {
+        Value::Int(i) => Ok(i),
+        _ => mismatch(op, index, "int"),
+    }
+}
+
+fn bool(op: Op, index: usize, value: Value) -> Result<bool, InterpError> {
+    match value {
+        Value::Bool(b) => Ok(b),
+        _ => mismatch(op, index, "bool"),
+    }
+}
+
+fn pointer(op: Op, index: usize, value: Value) -> Result<Pointer, InterpError> {
+    match value {
+        Value::Pointer(pointer) => Ok(pointer),
+        _ => mismatch(op, index, "Pointer"),
+    }
+}
+
+pub(crate) fn unary(op: Op, arg: Value) -> Result<Value, InterpError> {
+    match (op, arg) {
+        (Op::Not, Value::Bool(b)) => Ok(Value::Bool(!b)),
+        (Op::Char2int, Value::Char(c)) => Ok(Value::Int(c as i64)),
+        (Op::Int2char, Value::Int(i)) => match u32::try_from(i).ok().and_then(char::from_u32) {
+            Some(c) => Ok(Value::Char(c)),
+            None => error(format!("value {i} cannot be converted to char")),
+        },
+        (Op::Not, _) => mismatch(op, 0, "bool"),
+        (Op::Char2int, _) => mismatch(op, 0, "char"),
+        _ => mismatch(op, 0, "int"),
+    }
+}
+
+// Any binary operation, checking operands in the order `interp` does: `rhs` is only looked at
+// once `lhs` is known to be good, and not at all when `and` or `or` can stop at `lhs`.
+pub(crate) fn binary(op: Op, lhs: Value, rhs: Result<Value, InterpError>) -> Result<Value, InterpError> {
+    let r#type = match op {
+        Op::And | Op::Or => Type::Bool,
+        Op::Fadd
+        | Op::Fmul
+        | Op::Fsub
+        | Op::Fdiv
+        | Op::Feq
+        | Op::Flt
+        | Op::Fle
+        | Op::Fgt
+        | Op::Fge => Type::Float,
+        Op::Ceq | Op::Clt | Op::Cle | Op::Cgt | Op::Cge => Type::Char,
+        _ => Type::Int,
+    };
+    if !lhs.matches(&r#type) {
+        return mismatch(op, 0, &r#type.to_string());
+    }
+    match (op, lhs) {
+        (Op::And, Value::Bool(false)) => return Ok(Value::Bool(false)),
+        (Op::Or, Value::Bool(true)) => return Ok(Value::Bool(true)),
+        _ => {}
+    }
+    let rhs = rhs?;
+    if !rhs.matches(&r#type) {
+        return mismatch(op, 1, &r#type.to_string());
+    }
+
+    use Value::{Bool, Char, Float, Int};
+    Ok(match (op, lhs, rhs) {
+        (Op::Add, Int(a), Int(b)) => Int(a.wrapping_add(b)),
+        (Op::Mul, Int(a), Int(b)) => Int(a.wrapping_mul(b)),
+        (Op::Sub, Int(a), Int(b)) => Int(a.wrapping_sub(b)),
+        (Op::Div, Int(_), Int(0)) => return error("division by zero".to_string()),
+        (Op::Div, Int(a), Int(b)) => Int(a.wrapping_div(b)),
+        (Op::Eq, Int(a), Int(b)) => Bool(a == b),
+        (Op::Lt, Int(a), Int(b)) => Bool(a < b),
+        (Op::Gt, Int(a), Int(b)) => Bool(a > b),
+        (Op::Le, Int(a), Int(b)) => Bool(a <= b),
+        (Op::Ge, Int(a), Int(b)) => Bool(a >= b),
+        (Op::And, Bool(a), Bool(b)) => Bool(a && b),
+        (Op::Or, Bool(a), Bool(b)) => Bool(a || b),
+        (Op::Fadd, Float(a), Float(b)) => Float(a + b),
+        (Op::Fmul, Float(a), Float(b)) => Float(a * b),
+        (Op::Fsub, Float(a), Float(b)) => Float(a - b),
+        (Op::Fdiv, Float(a), Float(b)) => Float(a / b),
+        (Op::Feq, Float(a), Float(b)) => Bool(a == b),
+        (Op::Flt, Float(a), Float(b)) => Bool(a < b),
+        (Op::Fle, Float(a), Float(b)) => Bool(a <= b),
+        (Op::Fgt, Float(a), Float(b)) => Bool(a > b),
+        (Op::Fge, Float(a), Float(b)) => Bool(a >= b),
+        (Op::Ceq, Char(a), Char(b)) => Bool(a == b),
+        (Op::Clt, Char(a), Char(b)) => Bool(compare_chars(a, b).is_lt()),
+        (Op::Cle, Char(a), Char(b)) => Bool(compare_chars(a, b).is_le()),
+        (Op::Cgt, Char(a), Char(b)) => Bool(compare_chars(a, b).is_gt()),
+        (Op::Cge, Char(a), Char(b)) => Bool(compare_chars(a, b).is_ge()),
+        _ => unreachable!("operands of {op} were checked"),
+    })
+}
+
+enum Flow {
+    Next,
+    Jump(usize),
+    Return(Option<Value>),
+}
+
+struct Frame {
+    env: Vec<Option<Value>>,
+    last_label: Option<u32>,
+    current_label: Option<u32>,
+    speculation: Vec<Checkpoint>,
+}
+
+struct Checkpoint {
+    env: Vec<Option<Value>>,
+    last_label: Option<u32>,
+    current_label: Option<u32>,
+}
+
+struct Machine<'p, 'a, W> {
+    program: &'p Program<'a>,
+    out: W,
+    heap: Heap<'a>,
+    icount: u64,
+    max_instructions: u64,
+    depth: usize,
+    limits: Limits,
+}
+
+impl<'p, 'a, W: Write> Machine<'p, 'a, W> {
+    fn invoke(
+        &mut self,
+        index: usize,
+        env: Vec<Option<Value>>,
+    ) -> Result<Option<Value>, InterpError> {
+        let compiled = &self.program.functions[index];
+        let function = compiled.function;
+
+        self.depth += 1;
+        if let Some(max) = self.limits.call_depth {
+            if self.depth > max {
+                return Err(InterpError::exceeded(
+                    Limit::CallDepth,
+                    format!("call depth limit of {max} exceeded"),
+                ));
+            }
+        }
+
+        let mut frame = Frame {
+            env,
+            last_label: None,
+            current_label: None,
+            speculation: Vec::new(),
+        };
+        let mut pc = 0;
+        while let Some(code) = compiled.code.get(pc) {
+            if let Code::Label(label) = *code {
+                frame.last_label = frame.current_label;
+                frame.current_label = Some(label);
+                pc += 1;
+                continue;
+            }
+
+            match self.execute(compiled, code, &mut frame) {
+                Ok(Flow::Next) => pc += 1,
+                Ok(Flow::Jump(target)) => pc = target,
+                Ok(Flow::Return(value)) => {
+                    self.depth -= 1;
+                    return Ok(value);
+                }
+                Err(e) => return Err(e.locate(function, &function.instrs[pc])),
+            }
+        }
+
+        if !frame.speculation.is_empty() {
+            return Err(
+                InterpError::new("implicit return in speculative state".to_string())
+                    .locate(function, function.instrs.last().unwrap()),
+            );
+        }
+        self.depth -= 1;
+        Ok(None)
+    }
+
+    fn execute(
+        &mut self,
+        compiled: &'p Compiled<'a>,
+        code: &'p Code<'a>,
+        frame: &mut Frame,
+    ) -> Result<Flow, InterpError> {
+        self.icount += 1;
+        if self.icount > self.max_instructions {
+            return Err(InterpError::exceeded(
+                Limit::Instructions,
+                format!("instruction limit of {} exceeded", self.max_instructions),
+            ));
+        }
+
+        let env = &mut frame.env;
+        match *code {
+            Code::Label(_) => {}
+            Code::Const { dest, value } => env[dest as usize] = Some(value),
+            Code::Id { dest, arg } => env[dest as usize] = Some(compiled.get(env, arg)?),
+            Code::Unary { op, dest, arg } => {
+                env[dest as usize] = Some(unary(op, compiled.get(env, arg)?)?);
+            }
+            Code::Binary { op, dest, lhs, rhs } => {
+                use Value::{Bool, Int};
+                let a = compiled.get(env, lhs)?;
+                let value = match (op, a, env[rhs as usize]) {
+                    // The common cases first, without going through `binary`'s checks.
+                    (Op::Add, Int(a), Some(Int(b))) => Int(a.wrapping_add(b)),
+                    (Op::Mul, Int(a), Some(Int(b))) => Int(a.wrapping_mul(b)),
+                    (Op::Sub, Int(a), Some(Int(b))) => Int(a.wrapping_sub(b)),
+                    (Op::Eq, Int(a), Some(Int(b))) => Bool(a == b),
+                    (Op::Lt, Int(a), Some(Int(b))) => Bool(a < b),
+                    (Op::Gt, Int(a), Some(Int(b))) => Bool(a > b),
+                    (Op::Le, Int(a), Some(Int(b))) => Bool(a <= b),
+                    (Op::Ge, Int(a), Some(Int(b))) => Bool(a >= b),
+                    (op, a, b) => binary(op, a, b.ok_or_else(|| compiled.undefined(rhs)))?,
+                };
+                env[dest as usize] = Some(value);
+            }
+            Code::Jmp(target) => return Ok(Flow::Jump(target)),
+            Code::Br { cond, ref targets } => {
+                let cond = bool(Op::Br, 0, compiled.get(env, cond)?)?;
+                let target = &targets[if cond { 0 } else { 1 }];
+                return match target {
+                    Ok(target) => Ok(Flow::Jump(*target)),
+                    Err(message) => error(message.clone()),
+                };
+            }
+            Code::Ret(value) => {
+                if !frame.speculation.is_empty() {
+                    return error("ret not allowed during speculation".to_string());
+                }
+                let value = value.map(|slot| compiled.get(env, slot)).transpose()?;
+                return Ok(Flow::Return(value));
+            }
+            Code::Print(ref args) => {
+                let values = args
+                    .iter()
+                    .map(|&arg| compiled.get(env, arg).map(|value| value.to_string()))
+                    .collect::<Result<Vec<_>, _>>()?;
+                writeln!(self.out, "{}", values.join(" "))
+                    .or_else(|e| error(format!("cannot write output: {e}")))?;
+            }
+            Code::Nop => {}
+            Code::Call {
+                callee,
+                ref args,
+                dest,
+                instr,
+            } => {
+                if !frame.speculation.is_empty() {
+                    return error("call not allowed during speculation".to_string());
+                }
+                let target = &self.program.functions[callee];
+                let params = target.function.args.iter().flatten();
+                let mut callee_env = vec![None; target.names.len()];
+                for ((param, &slot), &arg) in params.zip(&target.params).zip(args.iter()) {
+                    let value = compiled.get(env, arg)?;
+                    if !value.matches(&param.r#type) {
+                        return error("function argument type mismatch".to_string());
+                    }
+                    callee_env[slot as usize] = Some(value);
+                }
+
+                let value = self.invoke(callee, callee_env)?;
+                if let Some(value) = returned(instr, target.function, value)? {
+                    frame.env[dest as usize] = Some(value);
+                }
+            }
+            Code::Phi { dest, ref sources } => {
+                let Some(last_label) = frame.last_label else {
+                    return error("phi node executed with no last label".to_string());
+                };
+                // Coming from a label the phi doesn't mention, or from an undefined argument, leaves `dest` undefined.
+                env[dest as usize] = sources
+                    .iter()
+                    .find(|(label, _)| *label == last_label)
+                    .and_then(|&(_, arg)| env[arg as usize]);
+            }
+            Code::Alloc { dest, size, instr } => {
+                let size = int(Op::Alloc, 0, compiled.get(env, size)?)?;
+                let function = compiled.function.name.as_str();
+                let pointer = self
+                    .heap
+                    .allocate(instr, size, function, self.limits.heap)?;
+                env[dest as usize] = Some(Value::Pointer(pointer));
+            }
+            Code::Free(arg) => {
+                self.heap
+                    .free(pointer(Op::Free, 0, compiled.get(env, arg)?)?)?;
+            }
+            Code::Store {
+                pointer: target,
+                value,
+            } => {
+                let target = pointer(Op::Store, 0, compiled.get(env, target)?)?;
+                let value = compiled.get(env, value)?;
+                if let Some(element) = self.heap.element(target) {
+                    if !value.matches(element) {
+                        return error(format!("store argument 1 must be a {element}"));
+                    }
+                }
+                self.heap.write(target, value)?;
+            }
+            Code::Load {
+                dest,
+                pointer: source,
+            } => {
+                let value = self
+                    .heap
+                    .read(pointer(Op::Load, 0, compiled.get(env, source)?)?)?;
+                match value {
+                    Some(value) => env[dest as usize] = Some(value),
+                    None => {
+                        let name = compiled.names[source as usize];
+                        return error(format!("Pointer {name} points to uninitialized data"));
+                    }
+                }
+            }
+            Code::Ptradd {
+                dest,
+                pointer: base,
+                offset,
+            } => {
+                let base = pointer(Op::Ptradd, 0, compiled.get(env, base)?)?;
+                let offset = int(Op::Ptradd, 1, compiled.get(env, offset)?)?;
+                env[dest as usize] = Some(Value::Pointer(Pointer {
+                    offset: base.offset.wrapping_add(offset),
+                    ..base
+                }));
+            }
+            Code::Speculate => {
+                frame.speculation.push(Checkpoint {
+                    env: frame.env.clone(),
+                    last_label: frame.last_label,
+                    current_label: frame.current_label,
+                });
+            }
+            Code::Commit => {
+                if frame.speculation.is_empty() {
+                    return error("commit in non-speculative state".to_string());
+                }
+                // Like brili, one commit ends every level of speculation.
+                frame.speculation.clear();
+            }
+            Code::Guard { cond, ref target } => {
+                if bool(Op::Guard, 0, compiled.get(env, cond)?)? {
+                    return Ok(Flow::Next);
+                }
+                let Some(checkpoint) = frame.speculation.pop() else {
+                    return error("abort in non-speculative state".to_string());
+                };
+                frame.env = checkpoint.env;
+                frame.last_label = checkpoint.last_label;
+                frame.current_label = checkpoint.current_label;
+                return match &**target {
+                    Ok(target) => Ok(Flow::Jump(*target)),
+                    Err(message) => error(message.clone()),
+                };
+            }
+            Code::Fail { op, ref message } => {
+                if matches!(op, Op::Call | Op::Ret) && !frame.speculation.is_empty() {
+                    return error(format!("{op} not allowed during speculation"));
+                }
+                return error(message.clone());
+            }
+        }
+        Ok(Flow::Next)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use insta::glob;
+
+    use super::run;
+    use crate::{
+        bench::source_args,
+        interp::{self, Limit, Limits},
+        parser::parse,
+    };
+
+    #[test]
+    fn test_bytecode() {
+        glob!(
+            "..",
+            "tests/test/interp/{core,char,float,mem,mixed,spec,ssa}/*.bril",
+            |path| {
+                let txt = std::fs::read_to_string(path).unwrap();
+                let expected = std::fs::read_to_string(path.with_extension("out")).unwrap();
+                let bril = parse(&txt).unwrap();
+                let args = source_args(&txt);
+
+                let mut out = Vec::new();
+                let profile = run(&bril, &args, &mut out, Limits::default()).unwrap();
+                assert_eq!(String::from_utf8(out).unwrap(), expected);
+
+                // Comparing the longest programs with the much slower `interp` isn't worth the time.
+                let limits = Limits {
+                    instructions: Some(1_000_000),
+                    ..Limits::default()
+                };
+                match interp::run_limited(&bril, &args, Vec::new(), limits) {
+                    Ok(reference) => assert_eq!(profile, reference),
+                    Err(e) => assert_eq!(e.limit, Some(Limit::Instructions)),
+                }
+            }
+        );
+    }
+
+    #[test]
+    fn test_bytecode_error() {
+        glob!(
+            "..",
+            "tests/test/interp-error/{core,char,mem,spec,ssa}-error/*.bril",
+            |path| {
+                let txt = std::fs::read_to_string(path).unwrap();
+                let bril = parse(&txt).unwrap();
+                let args = source_args(&txt);
+
+                let mut out = Vec::new();
+                let e = run(&bril, &args, &mut out, Limits::default()).unwrap_err();
+
+                let mut reference_out = Vec::new();
+                let reference = interp::run(&bril, &args, &mut reference_out).unwrap_err();
+                assert_eq!(e, reference);
+                assert_eq!(out, reference_out);
+            }
+        );
+    }
+
+    #[test]
+    fn test_bytecode_limits() {
+        let txt = "@main {\n  call @f;\n}\n@f {\n  n: int = const 2;\n  p: ptr<int> = alloc n;\n  call @f;\n}\n";
+        let bril = parse(txt).unwrap();
+        let cases = [
+            Limits {
+                instructions: Some(10),
+                ..Limits::default()
+            },
+            Limits {
+                heap: Some(7),
+                ..Limits::default()
+            },
+            Limits {
+                call_depth: Some(5),
+                ..Limits::default()
+            },
+        ];
+
+        for limits in cases {
+            let e = run(&bril, &[], Vec::new(), limits).unwrap_err();
+            let reference = interp::run_limited(&bril, &[], Vec::new(), limits).unwrap_err();
+            assert_eq!(e, reference);
+        }
+    }
+}
@@ -0,0 +1,403 @@
+use std::{
+    collections::{HashMap, HashSet},
+    fmt,
+};
+
+use crate::{
+    error::Location,
+    ir::{Bril, Function, Instruction, Op, Position, Type},
+};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TypeError {
+    pub function: String,
+    /// Index into `Function::instrs` of the offending instruction, `None` for the argument list.
+    pub index: Option<usize>,
+    pub pos: Option<Position>,
+    pub message: String,
+}
+
+impl fmt::Display for TypeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let at = Location {
+            function: &self.function,
+            index: self.index,
+            pos: self.pos,
+        };
+        write!(f, "{at}: {}", self.message)
+    }
+}
+
+/// Type checks every function of `bril`, returning all errors in program order.
+pub fn check(bril: &Bril) -> Vec<TypeError> {
+    let signatures = signatures(bril);
+    let mut errors = Vec::new();
+    for function in &bril.functions {
+        check_with(&signatures, function, &mut errors);
+    }
+    errors
+}
+
+/// Type checks `function`, calling the functions of `bril`. It needn't be one of them, as when a pass
+/// is still changing it.
+pub fn check_function(bril: &Bril, function: &Function) -> Vec<TypeError> {
+    let mut errors = Vec::new();
+    check_with(&signatures(bril), function, &mut errors);
+    errors
+}
+
+fn signatures(bril: &Bril) -> Signatures<'_> {
+    bril.functions
+        .iter()
+        .map(|function| {
+            let args = function
+                .args
+                .iter()
+                .flatten()
+                .map(|arg| &arg.r#type)
+                .collect();
+            (function.name.as_str(), (args, function.r#type.as_ref()))
+        })
+        .collect()
+}
+
+fn check_with(signatures: &Signatures, function: &Function, errors: &mut Vec<TypeError>) {
+    let mut checker = Checker {
+        function,
+        signatures,
+        vars: HashMap::new(),
+        labels: HashSet::new(),
+        errors,
+    };
+    checker.check();
+}
+
+// Operand and result types of an operation. `Var` is the type variable `T` of polymorphic operations.
+#[derive(Clone)]
+enum Pattern {
+    Exact(Type),
+    Var,
+    Ptr(Box<Pattern>),
+}
+
+impl Pattern {
+    fn unify(&self, r#type: &Type, var: &mut Option<Type>) -> bool {
+        match (self, r#type) {
+            (Pattern::Exact(expected), r#type) => expected == r#type,
+            (Pattern::Var, r#type) => match var {
+                Some(bound) => bound == r#type,
+                None => {
+                    *var = Some(r#type.clone());
+                    true
+                }
+            },
+            (Pattern::Ptr(inner), Type::Ptr(r#type)) => inner.unify(r#type, var),
+            (Pattern::Ptr(_), _) => false,
+        }
+    }
+
+    fn display(&self, var: &Option<Type>) -> String {
+        match (self, var) {
+            (Pattern::Exact(r#type), _) => r#type.to_string(),
+            (Pattern::Var, Some(bound)) => bound.to_string(),
+            (Pattern::Var, None) => "T".to_string(),
+            (Pattern::Ptr(inner), var) => format!("ptr<{}>", inner.display(var)),
+        }
+    }
+}
+
+struct Signature {
+    args: Vec<Pattern>,
+    dest: Option<Pattern>,
+    labels: usize,
+}
+
+fn signature(op: Op) -> Option<Signature> {
+    use Pattern::{Exact, Var};
+
+    let int = || Exact(Type::Int);
+    let bool = || Exact(Type::Bool);
+    let float = || Exact(Type::Float);
+    let char = || Exact(Type::Char);
+    let ptr = || Pattern::Ptr(Box::new(Var));
+
+    let (args, dest, labels) = match op {
+        Op::Add | Op::Mul | Op::Sub | Op::Div => (vec![int(), int()], Some(int()), 0),
+        Op::Eq | Op::Lt | Op::Gt | Op::Le | Op::Ge => (vec![int(), int()], Some(bool()), 0),
+        Op::Not => (vec![bool()], Some(bool()), 0),
+        Op::And | Op::Or => (vec![bool(), bool()], Some(bool()), 0),
+        Op::Jmp => (vec![], None, 1),
+        Op::Br => (vec![bool()], None, 2),
+        Op::Id => (vec![Var], Some(Var), 0),
+        Op::Nop | Op::Speculate | Op::Commit => (vec![], None, 0),
+        Op::Guard => (vec![bool()], None, 1),
+        Op::Fadd | Op::Fmul | Op::Fsub | Op::Fdiv => (vec![float(), float()], Some(float()), 0),
+        Op::Feq | Op::Flt | Op::Fle | Op::Fgt | Op::Fge => {
+            (vec![float(), float()], Some(bool()), 0)
+        }
+        Op::Alloc => (vec![int()], Some(ptr()), 0),
+        Op::Free => (vec![ptr()], None, 0),
+        Op::Store => (vec![ptr(), Var], None, 0),
+        Op::Load => (vec![ptr()], Some(Var), 0),
+        Op::Ptradd => (vec![ptr(), int()], Some(ptr()), 0),
+        Op::Ceq | Op::Clt | Op::Cle | Op::Cgt | Op::Cge => (vec![char(), char()], Some(bool()), 0),
+        Op::Char2int => (vec![char()], Some(int()), 0),
+        Op::Int2char => (vec![int()], Some(char()), 0),
+        Op::Const | Op::Call | Op::Ret | Op::Print | Op::Phi => return None,
+    };
+
+    Some(Signature { args, dest, labels })
+}
+
+type Signatures<'a> = HashMap<&'a str, (Vec<&'a Type>, Option<&'a Type>)>;
+
+struct Checker<'a, 'e> {
+    function: &'a Function,
+    signatures: &'a Signatures<'a>,
+    vars: HashMap<&'a str, &'a Type>,
+    labels: HashSet<&'a str>,
+    errors: &'e mut Vec<TypeError>,
+}
+
+impl<'a, 'e> Checker<'a, 'e> {
+    fn error(&mut self, index: Option<usize>, message: String) {
+        let pos = index.and_then(|index| self.function.instrs[index].pos());
+        self.errors.push(TypeError {
+            function: self.function.name.clone(),
+            index,
+            pos,
+            message,
+        });
+    }
+
+    fn define(&mut self, index: Option<usize>, var: &'a str, r#type: &'a Type) {
+        match self.vars.get(var) {
+            Some(old) if *old != r#type => {
+                let message = format!("new type {type} for {var} conflicts with old type {old}");
+                self.error(index, message);
+            }
+            Some(_) => {}
+            None => {
+                self.vars.insert(var, r#type);
+            }
+        }
+    }
+
+    fn check(&mut self) {
+        let function = self.function;
+
+        // Variables have one type throughout a function, so collect them all before checking any use.
+        for arg in function.args.iter().flatten() {
+            self.define(None, &arg.name, &arg.r#type);
+        }
+        for (index, instr) in function.instrs.iter().enumerate() {
+            if let Instruction::Label { label, .. } = instr {
+                if !self.labels.insert(label) {
+                    self.error(Some(index), format!("multiply defined label .{label}"));
+                }
+            } else if let (Some(dest), Some(r#type)) = (instr.dest(), instr.r#type()) {
+                self.define(Some(index), dest, r#type);
+            }
+        }
+
+        for (index, instr) in function.instrs.iter().enumerate() {
+            if let Some(op) = instr.op() {
+                self.instruction(index, op, instr);
+            }
+        }
+    }
+
+    fn instruction(&mut self, index: usize, op: Op, instr: &'a Instruction) {
+        let args = instr.args();
+        match op {
+            Op::Const => {}
+            Op::Print => {
+                if instr.dest().is_some() {
+                    self.error(Some(index), "print should have no result type".to_string());
+                }
+                self.defined(index, args);
+            }
+            Op::Ret => {
+                self.defined(index, args);
+                if instr.dest().is_some() {
+                    self.error(Some(index), "ret should have no result type".to_string());
+                }
+                match (self.function.r#type.as_ref(), args) {
+                    (Some(_), []) => self.error(
+                        Some(index),
+                        "missing return value in function with return type".to_string(),
+                    ),
+                    (Some(ret), [arg]) => {
+                        if let Some(r#type) = self.vars.get(arg.as_str()) {
+                            if *r#type != ret {
+                                let message = format!(
+                                    "{arg} has type {type}, but the function's return type is {ret}"
+                                );
+                                self.error(Some(index), message);
+                            }
+                        }
+                    }
+                    (Some(_), _) => {
+                        self.error(Some(index), "cannot return multiple values".to_string())
+                    }
+                    (None, []) => {}
+                    (None, _) => self.error(
+                        Some(index),
+                        "returning value in function without a return type".to_string(),
+                    ),
+                }
+            }
+            Op::Call => {
+                let funcs = instr.funcs();
+                if funcs.len() != 1 {
+                    let message = format!("call should have one function, not {}", funcs.len());
+                    self.error(Some(index), message);
+                    return;
+                }
+                let Some((params, ret)) = self.signatures.get(funcs[0].as_str()) else {
+                    self.error(Some(index), format!("function @{} undefined", funcs[0]));
+                    return;
+                };
+                let signature = Signature {
+                    args: params.iter().map(|&p| Pattern::Exact(p.clone())).collect(),
+                    dest: ret.map(|r| Pattern::Exact(r.clone())),
+                    labels: 0,
+                };
+                self.signature(index, instr, &signature, &format!("@{}", funcs[0]));
+            }
+            Op::Phi => {
+                let signature = Signature {
+                    args: vec![Pattern::Var; args.len()],
+                    dest: Some(Pattern::Var),
+                    labels: args.len(),
+                };
+                self.signature(index, instr, &signature, op.as_str());
+            }
+            op => {
+                let signature = signature(op).unwrap();
+                self.signature(index, instr, &signature, op.as_str());
+            }
+        }
+    }
+
+    fn defined(&mut self, index: usize, args: &[String]) {
+        for (i, arg) in args.iter().enumerate() {
+            if !self.vars.contains_key(arg.as_str()) {
+                self.error(Some(index), format!("{arg} (arg {i}) undefined"));
+            }
+        }
+    }
+
+    fn signature(&mut self, index: usize, instr: &Instruction, signature: &Signature, name: &str) {
+        let mut var = None;
+
+        match (instr.r#type(), &signature.dest) {
+            (Some(r#type), Some(dest)) => {
+                if !dest.unify(r#type, &mut var) {
+                    let message = format!(
+                        "result type of {name} should be {}, but found {type}",
+                        dest.display(&var)
+                    );
+                    self.error(Some(index), message);
+                }
+            }
+            (Some(_), None) => {
+                self.error(Some(index), format!("{name} should have no result type"));
+            }
+            (None, Some(dest)) => {
+                let message = format!("missing result type {} for {name}", dest.display(&var));
+                self.error(Some(index), message);
+            }
+            (None, None) => {}
+        }
+
+        let args = instr.args();
+        if args.len() != signature.args.len() {
+            let message = format!(
+                "{name} expects {} args, not {}",
+                signature.args.len(),
+                args.len()
+            );
+            self.error(Some(index), message);
+        } else {
+            for (i, (arg, pattern)) in args.iter().zip(&signature.args).enumerate() {
+                let Some(r#type) = self.vars.get(arg.as_str()).copied() else {
+                    self.error(Some(index), format!("{arg} (arg {i}) undefined"));
+                    continue;
+                };
+                if !pattern.unify(r#type, &mut var) {
+                    let message = format!(
+                        "{arg} has type {type}, but arg {i} for {name} should have type {}",
+                        pattern.display(&var)
+                    );
+                    self.error(Some(index), message);
+                }
+            }
+        }
+
+        let labels = instr.labels();
+        let op = instr.op().unwrap();
+        if labels.len() != signature.labels {
+            let message = format!(
+                "{op} needs {} labels; found {}",
+                signature.labels,
+                labels.len()
+            );
+            self.error(Some(index), message);
+        } else {
+            for label in labels {
+                if !self.labels.contains(label.as_str()) {
+                    self.error(Some(index), format!("label .{label} undefined"));
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use insta::glob;
+
+    use super::{check, check_function};
+    use crate::parser::parse;
+
+    #[test]
+    fn test_check() {
+        glob!("..", "tests/test/check/*.bril", |path| {
+            let txt = std::fs::read_to_string(path).unwrap();
+            let expected = std::fs::read_to_string(path.with_extension("err")).unwrap();
+
+            // `brilck` prints just the position of each error.
+            let output = match parse(&txt) {
+                Ok(bril) => check(&bril)
+                    .iter()
+                    .map(|e| format!("{}: {}\n", e.pos.unwrap(), e.message))
+                    .collect::<String>(),
+                // Ill-typed constants are already rejected by the parser.
+                Err(e) => {
+                    assert!(path.ends_with("badconst.bril"), "{e}");
+                    return;
+                }
+            };
+
+            assert_eq!(output, expected);
+        });
+    }
+
+    #[test]
+    fn test_check_function() {
+        let bril = parse("@main {\n}\n@f(x: int): int {\n  ret x;\n}\n").unwrap();
+        let function = parse("@main {\n  b: bool = const true;\n  y: int = call @f b;\n}\n")
+            .unwrap()
+            .functions
+            .remove(0);
+        let errors = check_function(&bril, &function);
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].function, "main");
+        assert_eq!(errors[0].index, Some(1));
+        assert_eq!(
+            errors[0].to_string(),
+            "@main[1] 3:3: b has type bool, but arg 0 for @f should have type int"
+        );
+    }
+}
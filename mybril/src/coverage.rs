@@ -0,0 +1,179 @@
+use std::{collections::HashMap, fmt};
+
+use crate::{
+    basic_block::partition,
+    interp::{InterpError, Observer, Value},
+    ir::{Bril, Function, Instruction, Op},
+};
+
+/// Instruction and branch hit counts of one program, accumulated over any number of runs
+/// observed through [`crate::interp::observe`].
+///
+/// Displays as the program's text with every line annotated by a `#` comment, so it still parses.
+pub struct Coverage<'a> {
+    bril: &'a Bril,
+    functions: HashMap<&'a str, usize>,
+    // Per function and instruction, labels included.
+    hits: Vec<Vec<u64>>,
+    // Per function and instruction, how often a `br` went to its first and its second label.
+    branches: Vec<Vec<(u64, u64)>>,
+}
+
+impl<'a> Coverage<'a> {
+    pub fn new(bril: &'a Bril) -> Self {
+        Self {
+            bril,
+            functions: bril
+                .functions
+                .iter()
+                .enumerate()
+                .map(|(index, function)| (function.name.as_str(), index))
+                .collect(),
+            hits: bril
+                .functions
+                .iter()
+                .map(|function| vec![0; function.instrs.len()])
+                .collect(),
+            branches: bril
+                .functions
+                .iter()
+                .map(|function| vec![(0, 0); function.instrs.len()])
+                .collect(),
+        }
+    }
+
+    // The comment after one instruction; a `br` is taken when its condition is true.
+    fn annotation(&self, function: usize, index: usize) -> String {
+        let hits = self.hits[function][index];
+        match self.bril.functions[function].instrs[index].op() {
+            Some(Op::Br) => {
+                let (taken, not_taken) = self.branches[function][index];
+                format!("# {hits} (taken {taken}, not taken {not_taken})")
+            }
+            _ => format!("# {hits}"),
+        }
+    }
+
+    fn summary(&self, function: usize) -> String {
+        let instrs = &self.bril.functions[function].instrs;
+        let hits = &self.hits[function];
+
+        let (mut instructions, mut covered_instructions) = (0, 0);
+        let (mut directions, mut covered_directions) = (0, 0);
+        for (index, instr) in instrs.iter().enumerate() {
+            if instr.label().is_some() {
+                continue;
+            }
+            instructions += 1;
+            covered_instructions += (hits[index] > 0) as usize;
+            if instr.op() == Some(Op::Br) {
+                let (taken, not_taken) = self.branches[function][index];
+                directions += 2;
+                covered_directions += (taken > 0) as usize + (not_taken > 0) as usize;
+            }
+        }
+
+        let mut start = 0;
+        let (mut blocks, mut covered_blocks) = (0, 0);
+        for block in partition(instrs) {
+            blocks += 1;
+            covered_blocks += (hits[start] > 0) as usize;
+            start += block.len();
+        }
+
+        format!(
+            "# @{}: {covered_instructions}/{instructions} instructions, {covered_blocks}/{blocks} blocks, {covered_directions}/{directions} branch directions",
+            self.bril.functions[function].name
+        )
+    }
+}
+
+impl<'a> Observer<'a> for Coverage<'a> {
+    fn instruction(
+        &mut self,
+        function: &'a Function,
+        index: usize,
+        env: &HashMap<&'a str, Value>,
+    ) -> Result<(), InterpError> {
+        let number = self.functions[function.name.as_str()];
+        self.hits[number][index] += 1;
+
+        let instr = &function.instrs[index];
+        if instr.op() == Some(Op::Br) {
+            // A condition that is missing or not a bool fails the run before it branches.
+            let branch = &mut self.branches[number][index];
+            match instr.args().first().and_then(|arg| env.get(arg.as_str())) {
+                Some(Value::Bool(true)) => branch.0 += 1,
+                Some(Value::Bool(false)) => branch.1 += 1,
+                _ => {}
+            }
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for Coverage<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (number, function) in self.bril.functions.iter().enumerate() {
+            let lines = function
+                .instrs
+                .iter()
+                .map(|instr| match instr {
+                    Instruction::Label { label, .. } => format!(".{label}:"),
+                    _ => format!("  {instr};"),
+                })
+                .collect::<Vec<_>>();
+            let width = lines.iter().map(String::len).max().unwrap_or(0);
+
+            writeln!(f, "{}", function.header())?;
+            for (index, line) in lines.iter().enumerate() {
+                writeln!(f, "{line:width$}  {}", self.annotation(number, index))?;
+            }
+            writeln!(f, "}}")?;
+            writeln!(f, "{}", self.summary(number))?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::Coverage;
+    use crate::{
+        interp::{observe, Limits},
+        parser::parse,
+    };
+
+    #[test]
+    fn test_coverage() {
+        let txt = "@main(n: int) {\n  zero: int = const 0;\n  neg: bool = lt n zero;\n  br neg .negative .done;\n.negative:\n  n: int = sub zero n;\n.done:\n  print n;\n}\n@unused {\n  ret;\n}\n";
+        let bril = parse(txt).unwrap();
+
+        let mut coverage = Coverage::new(&bril);
+        for n in ["3", "5"] {
+            let args = [n.to_string()];
+            observe(&bril, &args, Vec::new(), Limits::default(), &mut coverage).unwrap();
+        }
+
+        assert_eq!(
+            coverage.to_string(),
+            "\
+@main(n: int) {
+  zero: int = const 0;     # 2
+  neg: bool = lt n zero;   # 2
+  br neg .negative .done;  # 2 (taken 0, not taken 2)
+.negative:                 # 0
+  n: int = sub zero n;     # 0
+.done:                     # 2
+  print n;                 # 2
+}
+# @main: 4/5 instructions, 2/3 blocks, 1/2 branch directions
+@unused {
+  ret;  # 0
+}
+# @unused: 0/1 instructions, 0/1 blocks, 0/0 branch directions
+"
+        );
+        parse(&coverage.to_string()).unwrap();
+    }
+}
@@ -1,28 +1,41 @@
 use std::{
     collections::{hash_map::Entry, HashMap, HashSet},
+    fmt,
     marker::PhantomData,
 };
 
-use crate::{basic_block::BasicBlock, Argument, Function, Instruction};
+use crate::{
+    bytecode,
+    error::Error,
+    interp::{self, Value},
+    ir::{Function, Instruction, Literal, Op},
+    pass_manager::{Analysis, AnalysisManager},
+    printer::python_float,
+    ssa::Cfg,
+};
 
-trait Merger<S>
+/// Combines the facts flowing into a block from its neighbours.
+pub trait Merger<S>
 where
     S: 'static,
 {
     fn merge<'a, I: Iterator<Item = &'a S>>(&self, iter: I) -> S;
 }
 
-trait Tranfer<S> {
+/// The facts at one end of a basic block given those at the other end.
+pub trait Tranfer<S> {
     fn transfer(&self, instrs: &[Instruction], in_vars: &S) -> S;
 }
 
-struct Forward<S, M, T> {
+/// A dataflow problem solved from the entry along the edges of the control flow graph.
+pub struct Forward<S, M, T> {
     m: M,
     t: T,
     _s: PhantomData<S>,
 }
 
-struct BackWard<S, M, T> {
+/// A dataflow problem solved from the exits against the edges of the control flow graph.
+pub struct BackWard<S, M, T> {
     m: M,
     t: T,
     _s: PhantomData<S>,
@@ -34,52 +47,34 @@ where
     M: Merger<S>,
     T: Tranfer<S>,
 {
-    fn analyze(&self, blocks: &[BasicBlock], entry: &str, args: S) -> HashMap<String, (S, S)> {
-        let labels: Vec<&str> = blocks
-            .iter()
-            .map(|block| block[0].label.as_deref().unwrap())
-            .collect();
+    pub const fn new(m: M, t: T) -> Self {
+        Self {
+            m,
+            t,
+            _s: PhantomData,
+        }
+    }
 
-        let label_map = blocks
-            .iter()
-            .map(|block| (block[0].label.as_deref().unwrap(), block))
+    /// The facts at the start and at the end of each block, by label, with `args` at the entry.
+    pub fn analyze(&self, cfg: &Cfg, args: S) -> HashMap<String, (S, S)> {
+        let label_map = cfg
+            .blocks()
+            .map(|block| (block[0].label().unwrap(), block))
             .collect::<HashMap<_, _>>();
-
-        let (predecessors, successors) = {
-            let mut predecessors = HashMap::new();
-            let mut successors = HashMap::new();
-
-            for b in blocks {
-                let start = b[0].label.as_deref().unwrap();
-
-                if let Some(labels) = b.last().unwrap().labels.as_ref() {
-                    for dest in labels {
-                        let dest = dest.as_str();
-                        successors
-                            .entry(start)
-                            .or_insert_with(HashSet::new)
-                            .insert(dest);
-                        predecessors
-                            .entry(dest)
-                            .or_insert_with(HashSet::new)
-                            .insert(start);
-                    }
-                }
-            }
-            (predecessors, successors)
-        };
+        let labels: Vec<&str> = cfg
+            .blocks()
+            .map(|block| block[0].label().unwrap())
+            .collect();
 
         let mut result: HashMap<String, (S, S)> = HashMap::new();
         let mut work_list = labels.clone();
 
         while let Some(label) = work_list.pop() {
             let in_vars: S = self.m.merge(
-                predecessors
-                    .get(label)
-                    .unwrap_or(&Default::default())
+                cfg.predecessors(label)
                     .iter()
-                    .flat_map(|&p| result.get(p).into_iter().map(|(_, out_vars)| out_vars))
-                    .chain(if label == entry { Some(&args) } else { None }.into_iter()),
+                    .flat_map(|p| result.get(p).into_iter().map(|(_, out_vars)| out_vars))
+                    .chain((label == cfg.entry()).then_some(&args)),
             );
 
             let out_vars = self.t.transfer(label_map[label], &in_vars);
@@ -101,13 +96,7 @@ where
                 }
             };
             if updated {
-                work_list.extend(
-                    successors
-                        .get(label)
-                        .into_iter()
-                        .flat_map(HashSet::iter)
-                        .cloned(),
-                );
+                work_list.extend(cfg.successors(label).iter().map(String::as_str));
             }
         }
 
@@ -121,51 +110,33 @@ where
     M: Merger<S>,
     T: Tranfer<S>,
 {
-    fn analyze(&self, blocks: &[BasicBlock]) -> HashMap<String, (S, S)> {
-        let labels: Vec<&str> = blocks
-            .iter()
-            .map(|block| block[0].label.as_deref().unwrap())
-            .collect();
+    pub const fn new(m: M, t: T) -> Self {
+        Self {
+            m,
+            t,
+            _s: PhantomData,
+        }
+    }
 
-        let label_map = blocks
-            .iter()
-            .map(|block| (block[0].label.as_deref().unwrap(), block))
+    /// The facts at the start and at the end of each block, by label.
+    pub fn analyze(&self, cfg: &Cfg) -> HashMap<String, (S, S)> {
+        let label_map = cfg
+            .blocks()
+            .map(|block| (block[0].label().unwrap(), block))
             .collect::<HashMap<_, _>>();
-
-        let (predecessors, successors) = {
-            let mut predecessors = HashMap::new();
-            let mut successors = HashMap::new();
-
-            for b in blocks {
-                let start = b[0].label.as_deref().unwrap();
-
-                if let Some(labels) = b.last().unwrap().labels.as_ref() {
-                    for dest in labels {
-                        let dest = dest.as_str();
-                        successors
-                            .entry(start)
-                            .or_insert_with(HashSet::new)
-                            .insert(dest);
-                        predecessors
-                            .entry(dest)
-                            .or_insert_with(HashSet::new)
-                            .insert(start);
-                    }
-                }
-            }
-            (predecessors, successors)
-        };
+        let labels: Vec<&str> = cfg
+            .blocks()
+            .map(|block| block[0].label().unwrap())
+            .collect();
 
         let mut result: HashMap<String, (S, S)> = HashMap::new();
         let mut work_list = labels.clone();
 
         while let Some(label) = work_list.pop() {
             let out_vars = self.m.merge(
-                successors
-                    .get(label)
-                    .unwrap_or(&Default::default())
+                cfg.successors(label)
                     .iter()
-                    .flat_map(|&p| result.get(p).into_iter().map(|(in_vars, _)| in_vars)),
+                    .flat_map(|p| result.get(p).into_iter().map(|(in_vars, _)| in_vars)),
             );
 
             let in_vars = self.t.transfer(label_map[label], &out_vars);
@@ -187,13 +158,7 @@ where
                 }
             };
             if updated {
-                work_list.extend(
-                    predecessors
-                        .get(label)
-                        .into_iter()
-                        .flat_map(HashSet::iter)
-                        .cloned(),
-                );
+                work_list.extend(cfg.predecessors(label).iter().map(String::as_str));
             }
         }
 
@@ -201,7 +166,8 @@ where
     }
 }
 
-struct UnionMerger;
+/// Merges sets by union.
+pub struct UnionMerger;
 struct DefinedTransfer;
 struct UsedTransfer;
 
@@ -221,8 +187,8 @@ impl Tranfer<HashSet<String>> for DefinedTransfer {
     fn transfer(&self, instrs: &[Instruction], vars: &HashSet<String>) -> HashSet<String> {
         let mut result = vars.clone();
         for instr in instrs {
-            if let Some(dest) = &instr.dest {
-                result.insert(dest.clone());
+            if let Some(dest) = instr.dest() {
+                result.insert(dest.to_string());
             }
         }
         result
@@ -231,23 +197,27 @@ impl Tranfer<HashSet<String>> for DefinedTransfer {
 
 impl Tranfer<HashSet<String>> for UsedTransfer {
     fn transfer(&self, instrs: &[Instruction], vars: &HashSet<String>) -> HashSet<String> {
-        let mut result = vars.clone();
+        let mut used = HashSet::new();
         let mut defined = HashSet::new();
         for instr in instrs {
-            if let Some(args) = &instr.args {
-                result.extend(
-                    args.iter()
-                        .filter(|v| !defined.contains(v.as_str()))
-                        .cloned(),
-                );
-            }
+            used.extend(
+                instr
+                    .args()
+                    .iter()
+                    .filter(|v| !defined.contains(v.as_str()))
+                    .cloned(),
+            );
 
-            if let Some(dest) = &instr.dest {
-                defined.insert(dest.as_str());
-                result.remove(dest.as_str());
+            if let Some(dest) = instr.dest() {
+                defined.insert(dest);
             }
         }
-        result
+        used.extend(
+            vars.iter()
+                .filter(|v| !defined.contains(v.as_str()))
+                .cloned(),
+        );
+        used
     }
 }
 
@@ -263,45 +233,322 @@ const LIVE: BackWard<HashSet<String>, UnionMerger, UsedTransfer> = BackWard {
     _s: PhantomData,
 };
 
+/// The variables defined on some path to the start and to the end of each block, not counting arguments.
+pub struct Defined(pub HashMap<String, (HashSet<String>, HashSet<String>)>);
+
+impl Analysis for Defined {
+    fn compute(function: &Function, analyses: &mut AnalysisManager) -> Result<Self, Error> {
+        Ok(Self(
+            DEFINED.analyze(&*analyses.get::<Cfg>(function)?, HashSet::new()),
+        ))
+    }
+}
+
+/// The variables live at the start and at the end of each block.
+pub struct Live(pub HashMap<String, (HashSet<String>, HashSet<String>)>);
+
+impl Analysis for Live {
+    fn compute(function: &Function, analyses: &mut AnalysisManager) -> Result<Self, Error> {
+        Ok(Self(LIVE.analyze(&*analyses.get::<Cfg>(function)?)))
+    }
+}
+
+/// What constant propagation knows of a variable: nothing yet, its one value, or that it can have
+/// different values.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Constant {
+    Unknown,
+    Value(Literal),
+    Conflicting,
+}
+
+/// What is known of each variable at a point of the program.
+pub type Known = HashMap<String, Constant>;
+
+impl Constant {
+    /// What is known of a variable that has `self` on one path and `other` on another.
+    pub fn meet(self, other: Self) -> Self {
+        match (self, other) {
+            (Constant::Unknown, c) | (c, Constant::Unknown) => c,
+            (Constant::Value(a), Constant::Value(b)) if a == b => Constant::Value(a),
+            _ => Constant::Conflicting,
+        }
+    }
+
+    /// The value `instr` gives its destination when its arguments have the values in `known`,
+    /// folding operations on constants the way the interpreter evaluates them. Operations that would
+    /// fail, like dividing by zero, are left to fail when the program runs.
+    pub fn of(instr: &Instruction, known: &Known) -> Self {
+        let op = match instr {
+            Instruction::Constant { value, .. } => return Constant::Value(*value),
+            Instruction::Value { op, .. } => *op,
+            _ => return Constant::Conflicting,
+        };
+        let unary = matches!(op, Op::Id | Op::Not | Op::Char2int | Op::Int2char);
+        let binary = interp::arity(op) == Some(2) && !matches!(op, Op::Ptradd | Op::Store);
+        if !unary && !binary {
+            return Constant::Conflicting;
+        }
+
+        let mut args = Vec::new();
+        for arg in instr.args() {
+            match known.get(arg).copied().unwrap_or(Constant::Unknown) {
+                Constant::Value(literal) => args.push(Value::from(&literal)),
+                // An undefined argument makes the instruction fail, so any value will do for now.
+                Constant::Unknown => return Constant::Unknown,
+                Constant::Conflicting => return Constant::Conflicting,
+            }
+        }
+        let value = match (op, args.as_slice()) {
+            (Op::Id, &[value]) => Ok(value),
+            (_, &[value]) if unary => bytecode::unary(op, value),
+            (_, &[lhs, rhs]) if binary => bytecode::binary(op, lhs, Ok(rhs)),
+            _ => return Constant::Conflicting,
+        };
+        match value {
+            Ok(Value::Int(i)) => Constant::Value(Literal::Int(i)),
+            Ok(Value::Bool(b)) => Constant::Value(Literal::Bool(b)),
+            // Bril JSON has no infinities or NaN to write them as.
+            Ok(Value::Float(x)) if x.is_finite() => Constant::Value(Literal::Float(x)),
+            Ok(Value::Char(c)) => Constant::Value(Literal::Char(c)),
+            Ok(Value::Float(_) | Value::Pointer(_)) | Err(_) => Constant::Conflicting,
+        }
+    }
+}
+
+/// Prints a constant the way bril's `df.py` does, with Python's `repr` and `?` when conflicting.
+impl fmt::Display for Constant {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Constant::Unknown => write!(f, "unknown"),
+            Constant::Value(Literal::Int(i)) => write!(f, "{i}"),
+            Constant::Value(Literal::Bool(true)) => write!(f, "True"),
+            Constant::Value(Literal::Bool(false)) => write!(f, "False"),
+            Constant::Value(Literal::Float(x)) => write!(f, "{}", python_float(*x)),
+            Constant::Value(Literal::Char(c)) => write!(f, "{c}"),
+            Constant::Conflicting => write!(f, "?"),
+        }
+    }
+}
+
+/// Meets what each predecessor knows, a variable missing from one being unknown there.
+pub struct ConstantMerger;
+struct ConstantTransfer;
+
+impl Merger<Known> for ConstantMerger {
+    fn merge<'a, I>(&self, iter: I) -> Known
+    where
+        I: Iterator<Item = &'a Known>,
+    {
+        iter.fold(HashMap::new(), |mut acc, known| {
+            for (var, constant) in known {
+                let merged = acc.entry(var.clone()).or_insert(Constant::Unknown);
+                *merged = merged.meet(*constant);
+            }
+            acc
+        })
+    }
+}
+
+impl Tranfer<Known> for ConstantTransfer {
+    fn transfer(&self, instrs: &[Instruction], known: &Known) -> Known {
+        let mut known = known.clone();
+        for instr in instrs {
+            if let Some(dest) = instr.dest() {
+                let constant = Constant::of(instr, &known);
+                known.insert(dest.to_string(), constant);
+            }
+        }
+        known
+    }
+}
+
+const CONSTANTS: Forward<Known, ConstantMerger, ConstantTransfer> =
+    Forward::new(ConstantMerger, ConstantTransfer);
+
+/// What is known of each variable at the start and at the end of each block, given what is known
+/// at the entry.
+pub fn constants(cfg: &Cfg, entry: Known) -> HashMap<String, (Known, Known)> {
+    CONSTANTS.analyze(cfg, entry)
+}
+
+/// [`constants`] with the arguments conflicting at the entry, as callers may pass anything.
+pub struct Constants(pub HashMap<String, (Known, Known)>);
+
+impl Analysis for Constants {
+    fn compute(function: &Function, analyses: &mut AnalysisManager) -> Result<Self, Error> {
+        let args = function.args.iter().flatten();
+        let entry = args
+            .map(|arg| (arg.name.clone(), Constant::Conflicting))
+            .collect();
+        Ok(Self(constants(&*analyses.get::<Cfg>(function)?, entry)))
+    }
+}
+
+/// A definition of a variable: the function's argument at a position, or an instruction, by the
+/// label of its block in the control flow graph and its index there, the label being 0.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum Def {
+    Arg(usize),
+    Instr(String, usize),
+}
+
+/// The definitions of each variable that reach a point of the program.
+pub type Reaching = HashMap<String, HashSet<Def>>;
+
+/// Merges the definitions of each variable by union.
+pub struct ReachingMerger;
+struct ReachingTransfer;
+
+impl Merger<Reaching> for ReachingMerger {
+    fn merge<'a, I>(&self, iter: I) -> Reaching
+    where
+        I: Iterator<Item = &'a Reaching>,
+    {
+        iter.fold(HashMap::new(), |mut acc, reaching| {
+            for (var, defs) in reaching {
+                acc.entry(var.clone())
+                    .or_default()
+                    .extend(defs.iter().cloned());
+            }
+            acc
+        })
+    }
+}
+
+// Calls `visit` with each instruction of a block and the definitions reaching it, and returns those
+// reaching the end of the block.
+fn reach(
+    instrs: &[Instruction],
+    reaching: &Reaching,
+    mut visit: impl FnMut(usize, &Instruction, &Reaching),
+) -> Reaching {
+    let label = instrs[0].label().unwrap();
+    let mut reaching = reaching.clone();
+    for (index, instr) in instrs.iter().enumerate() {
+        visit(index, instr, &reaching);
+        if let Some(dest) = instr.dest() {
+            reaching.insert(
+                dest.to_string(),
+                HashSet::from([Def::Instr(label.to_string(), index)]),
+            );
+        }
+    }
+    reaching
+}
+
+impl Tranfer<Reaching> for ReachingTransfer {
+    fn transfer(&self, instrs: &[Instruction], reaching: &Reaching) -> Reaching {
+        reach(instrs, reaching, |_, _, _| {})
+    }
+}
+
+const REACHING: Forward<Reaching, ReachingMerger, ReachingTransfer> =
+    Forward::new(ReachingMerger, ReachingTransfer);
+
+/// The definitions reaching the start and the end of each block, the arguments reaching the entry.
+pub struct ReachingDefinitions(pub HashMap<String, (Reaching, Reaching)>);
+
+impl Analysis for ReachingDefinitions {
+    fn compute(function: &Function, analyses: &mut AnalysisManager) -> Result<Self, Error> {
+        let args = function.args.iter().flatten().enumerate();
+        let entry = args
+            .map(|(i, arg)| (arg.name.clone(), HashSet::from([Def::Arg(i)])))
+            .collect();
+        Ok(Self(
+            REACHING.analyze(&*analyses.get::<Cfg>(function)?, entry),
+        ))
+    }
+}
+
+/// The use-def and def-use chains of a function: which definitions reach each use of a variable, and
+/// which instructions use each definition. Instructions are named as in [`Def::Instr`].
+#[derive(Debug, Default)]
+pub struct Chains {
+    use_def: HashMap<(String, usize, String), HashSet<Def>>,
+    def_use: HashMap<Def, HashSet<(String, usize)>>,
+}
+
+impl Chains {
+    /// The definitions of `var` reaching the instruction at `index` of block `label`, none if `var`
+    /// may be undefined there on every path or the instruction does not use it.
+    pub fn defs(&self, label: &str, index: usize, var: &str) -> impl Iterator<Item = &Def> {
+        self.use_def
+            .get(&(label.to_string(), index, var.to_string()))
+            .into_iter()
+            .flatten()
+    }
+
+    /// The instructions using the value `def` gives its variable.
+    pub fn uses(&self, def: &Def) -> impl Iterator<Item = &(String, usize)> {
+        self.def_use.get(def).into_iter().flatten()
+    }
+}
+
+impl Analysis for Chains {
+    fn compute(function: &Function, analyses: &mut AnalysisManager) -> Result<Self, Error> {
+        let reaching = analyses.get::<ReachingDefinitions>(function)?;
+        let cfg = analyses.get::<Cfg>(function)?;
+
+        let mut chains = Chains::default();
+        for block in cfg.blocks() {
+            let label = block[0].label().unwrap();
+            reach(block, &reaching.0[label].0, |index, instr, reaching| {
+                for var in instr.args() {
+                    let defs = reaching.get(var).cloned().unwrap_or_default();
+                    for def in &defs {
+                        chains
+                            .def_use
+                            .entry(def.clone())
+                            .or_default()
+                            .insert((label.to_string(), index));
+                    }
+                    chains
+                        .use_def
+                        .insert((label.to_string(), index, var.clone()), defs);
+                }
+            });
+        }
+        Ok(chains)
+    }
+}
+
 #[cfg(test)]
 mod test {
-    use std::collections::{BTreeSet, HashSet};
+    use std::collections::BTreeSet;
 
-    use insta::{assert_display_snapshot, glob};
+    use insta::{assert_snapshot, glob};
 
     use crate::{
-        basic_block::BasicBlock,
-        dataflow::{DEFINED, LIVE},
+        dataflow::{Chains, Def, DEFINED, LIVE},
+        ir::Bril,
+        parser::parse,
+        pass_manager::AnalysisManager,
+        ssa::Cfg,
         test::bril2json,
-        Bril, Instruction,
     };
 
     #[test]
     fn test_defined_generic() {
         glob!("..", "tests/examples/df/*.bril", |path| {
-            let txt = std::fs::read_to_string(&path).unwrap();
+            let txt = std::fs::read_to_string(path).unwrap();
             let json = bril2json(&txt);
             let mut bril: Bril = serde_json::from_str(&json).unwrap();
 
             let mut output = String::new();
 
             for func in &mut bril.functions {
-                let basic_blocks = BasicBlock::new_blocks(func.instrs.as_slice());
+                let cfg = Cfg::new(func).unwrap();
                 let mut defined = DEFINED.analyze(
-                    &basic_blocks,
-                    basic_blocks[0][0].label.as_deref().unwrap(),
+                    &cfg,
                     func.args
                         .as_ref()
                         .map(|a| a.iter().map(|a| a.name.clone()).collect())
                         .unwrap_or_default(),
                 );
-                let instrs = basic_blocks
-                    .into_iter()
-                    .flat_map(|b| Into::<Vec<Instruction>>::into(b).into_iter())
-                    .collect::<Vec<_>>();
-                let labels = instrs
-                    .iter()
-                    .filter_map(|i| i.label.as_deref())
+                let labels = cfg
+                    .blocks()
+                    .map(|b| b[0].label().unwrap())
                     .collect::<Vec<_>>();
                 let defined = labels
                     .into_iter()
@@ -313,39 +560,35 @@ mod test {
                     output.push_str(&format!("  {}:\n", label));
                     output.push_str(&format!(
                         "    in: {:?}\n",
-                        var_in.into_iter().collect::<BTreeSet<_>>()
+                        var_in.iter().collect::<BTreeSet<_>>()
                     ));
                     output.push_str(&format!(
                         "    out: {:?}\n",
-                        var_out.into_iter().collect::<BTreeSet<_>>()
+                        var_out.iter().collect::<BTreeSet<_>>()
                     ));
                 }
-                output.push_str("\n");
+                output.push('\n');
             }
 
-            assert_display_snapshot!(format!("{txt}\n{output}"));
+            assert_snapshot!(format!("{txt}\n{output}"));
         });
     }
 
     #[test]
     fn test_live_generic() {
         glob!("..", "tests/examples/df/*.bril", |path| {
-            let txt = std::fs::read_to_string(&path).unwrap();
+            let txt = std::fs::read_to_string(path).unwrap();
             let json = bril2json(&txt);
             let mut bril: Bril = serde_json::from_str(&json).unwrap();
 
             let mut output = String::new();
 
             for func in &mut bril.functions {
-                let basic_blocks = BasicBlock::new_blocks(func.instrs.as_slice());
-                let mut defined = LIVE.analyze(&basic_blocks);
-                let instrs = basic_blocks
-                    .into_iter()
-                    .flat_map(|b| Into::<Vec<Instruction>>::into(b).into_iter())
-                    .collect::<Vec<_>>();
-                let labels = instrs
-                    .iter()
-                    .filter_map(|i| i.label.as_deref())
+                let cfg = Cfg::new(func).unwrap();
+                let mut defined = LIVE.analyze(&cfg);
+                let labels = cfg
+                    .blocks()
+                    .map(|b| b[0].label().unwrap())
                     .collect::<Vec<_>>();
                 let defined = labels
                     .into_iter()
@@ -357,17 +600,70 @@ mod test {
                     output.push_str(&format!("  {}:\n", label));
                     output.push_str(&format!(
                         "    in: {:?}\n",
-                        var_in.into_iter().collect::<BTreeSet<_>>()
+                        var_in.iter().collect::<BTreeSet<_>>()
                     ));
                     output.push_str(&format!(
                         "    out: {:?}\n",
-                        var_out.into_iter().collect::<BTreeSet<_>>()
+                        var_out.iter().collect::<BTreeSet<_>>()
                     ));
                 }
-                output.push_str("\n");
+                output.push('\n');
+            }
+
+            assert_snapshot!(format!("{txt}\n{output}"));
+        });
+    }
+
+    #[test]
+    fn test_chains() {
+        glob!("..", "tests/examples/df/*.bril", |path| {
+            let txt = std::fs::read_to_string(path).unwrap();
+            let bril = parse(&txt).unwrap();
+
+            let name = |def: &Def| match def {
+                Def::Arg(i) => format!("arg {i}"),
+                Def::Instr(label, index) => format!(".{label}[{index}]"),
+            };
+            let mut output = String::new();
+            for func in &bril.functions {
+                let mut analyses = AnalysisManager::default();
+                let cfg = analyses.get::<Cfg>(func).unwrap();
+                let chains = analyses.get::<Chains>(func).unwrap();
+
+                output.push_str(&format!("{}:\n", func.name));
+                let args = func.args.iter().flatten().enumerate();
+                let defs = args
+                    .map(|(i, _)| Def::Arg(i))
+                    .chain(cfg.blocks().flat_map(|b| {
+                        let label = b[0].label().unwrap();
+                        (0..b.len())
+                            .filter(|&i| b[i].dest().is_some())
+                            .map(|i| Def::Instr(label.to_string(), i))
+                    }));
+                for def in defs {
+                    let uses = chains
+                        .uses(&def)
+                        .map(|(label, index)| name(&Def::Instr(label.clone(), *index)))
+                        .collect::<BTreeSet<_>>();
+                    output.push_str(&format!("  {} used by {uses:?}\n", name(&def)));
+                }
+                for b in cfg.blocks() {
+                    let label = b[0].label().unwrap();
+                    for (i, instr) in b.iter().enumerate() {
+                        for var in instr.args() {
+                            let defs = chains
+                                .defs(label, i, var)
+                                .map(name)
+                                .collect::<BTreeSet<_>>();
+                            output
+                                .push_str(&format!("  .{label}[{i}] {var} defined by {defs:?}\n"));
+                        }
+                    }
+                }
+                output.push('\n');
             }
 
-            assert_display_snapshot!(format!("{txt}\n{output}"));
+            assert_snapshot!(format!("{txt}\n{output}"));
         });
     }
 }
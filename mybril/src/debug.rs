@@ -0,0 +1,398 @@
+use std::{
+    collections::HashMap,
+    io::{BufRead, Write},
+};
+
+use crate::{
+    interp::{InterpError, Observer, Value},
+    ir::{Bril, Function},
+};
+
+const HELP: &str = "\
+step [n]            run n instructions (default 1), entering calls
+continue            run until a breakpoint or watchpoint stops the program
+break [@f] .label   stop at a label of @f (default: the current function)
+break [@f] index    stop at an instruction of @f, counting labels
+delete n            remove breakpoint n
+watch var           stop when var changes in any call of the current function
+print var...        show variables of the current frame
+backtrace           show the call stack, innermost first
+quit                stop the program
+";
+
+/// A step debugger driven by text commands, one per line of `commands`; `help` lists them.
+/// The program stops before its first instruction, and runs to the end once the commands run out.
+pub struct Debugger<'a, R, W> {
+    bril: &'a Bril,
+    commands: R,
+    out: W,
+    // Breakpoints by number, as a function and an instruction index. Deleted ones are `None`.
+    breakpoints: Vec<Option<(&'a str, usize)>>,
+    watchpoints: Vec<Watchpoint<'a>>,
+    // The function and current instruction of each frame, innermost last.
+    stack: Vec<(&'a Function, usize)>,
+    // Instructions left to run before stopping, or `None` to run until something else stops.
+    steps: Option<usize>,
+    detached: bool,
+}
+
+struct Watchpoint<'a> {
+    function: &'a str,
+    var: String,
+    // The last value seen in each frame of `function`, by depth in the stack, so that recursive
+    // calls don't compare their variables with each other's.
+    values: HashMap<usize, Option<Value>>,
+}
+
+impl<'a, R: BufRead, W: Write> Debugger<'a, R, W> {
+    pub fn new(bril: &'a Bril, commands: R, out: W) -> Self {
+        Self {
+            bril,
+            commands,
+            out,
+            breakpoints: Vec::new(),
+            watchpoints: Vec::new(),
+            stack: Vec::new(),
+            steps: Some(0),
+            detached: false,
+        }
+    }
+
+    fn write(&mut self, text: &str) -> Result<(), InterpError> {
+        writeln!(self.out, "{text}")
+            .map_err(|e| InterpError::new(format!("cannot write debugger output: {e}")))
+    }
+
+    fn location(&self, function: &Function, index: usize) -> String {
+        match function.instrs.get(index) {
+            Some(instr) => format!("@{}[{index}]: {instr}", function.name),
+            None => format!("@{}[{index}]", function.name),
+        }
+    }
+
+    // Reads and runs commands until one resumes the program.
+    fn prompt(&mut self, env: &HashMap<&'a str, Value>) -> Result<(), InterpError> {
+        loop {
+            let mut line = String::new();
+            let read = self
+                .commands
+                .read_line(&mut line)
+                .map_err(|e| InterpError::new(format!("cannot read debugger command: {e}")))?;
+            if read == 0 {
+                self.detached = true;
+                return Ok(());
+            }
+
+            let words = line.split_whitespace().collect::<Vec<_>>();
+            let Some((&command, args)) = words.split_first() else {
+                continue;
+            };
+            match (command, args) {
+                ("step" | "s", []) => {
+                    self.steps = Some(1);
+                    return Ok(());
+                }
+                ("step" | "s", [n]) => match n.parse::<usize>() {
+                    Ok(n) if n > 0 => {
+                        self.steps = Some(n);
+                        return Ok(());
+                    }
+                    _ => self.write(&format!("cannot step {n} instructions"))?,
+                },
+                ("continue" | "c", []) => {
+                    self.steps = None;
+                    return Ok(());
+                }
+                ("break" | "b", args) => {
+                    let message = match self.breakpoint(args) {
+                        Ok((function, index)) => {
+                            self.breakpoints.push(Some((function, index)));
+                            let function = self.function(function).unwrap();
+                            format!(
+                                "breakpoint {} at {}",
+                                self.breakpoints.len(),
+                                self.location(function, index)
+                            )
+                        }
+                        Err(message) => message,
+                    };
+                    self.write(&message)?;
+                }
+                ("delete" | "d", [n]) => {
+                    let slot = n
+                        .parse::<usize>()
+                        .ok()
+                        .and_then(|n| self.breakpoints.get_mut(n.wrapping_sub(1)))
+                        .filter(|slot| slot.is_some());
+                    match slot {
+                        Some(slot) => *slot = None,
+                        None => self.write(&format!("no breakpoint {n}"))?,
+                    }
+                }
+                ("watch" | "w", [var]) => {
+                    let (function, _) = *self.stack.last().unwrap();
+                    let depth = self.stack.len() - 1;
+                    self.watchpoints.push(Watchpoint {
+                        function: &function.name,
+                        var: var.to_string(),
+                        values: HashMap::from([(depth, env.get(var).copied())]),
+                    });
+                    self.write(&format!("watching {var} in @{}", function.name))?;
+                }
+                ("print" | "p", vars) if !vars.is_empty() => {
+                    for var in vars {
+                        let message = match env.get(var) {
+                            Some(value) => format!("{var} = {value}"),
+                            None => format!("{var} is undefined"),
+                        };
+                        self.write(&message)?;
+                    }
+                }
+                ("backtrace" | "bt", []) => {
+                    let frames = self
+                        .stack
+                        .iter()
+                        .rev()
+                        .enumerate()
+                        .map(|(depth, (function, index))| {
+                            format!("#{depth} {}", self.location(function, *index))
+                        })
+                        .collect::<Vec<_>>();
+                    for frame in frames {
+                        self.write(&frame)?;
+                    }
+                }
+                ("quit" | "q", []) => {
+                    return Err(InterpError::new("stopped by the debugger".to_string()))
+                }
+                ("help" | "h", []) => self.write(HELP.trim_end())?,
+                _ => self.write(&format!("cannot understand `{}`; try `help`", line.trim()))?,
+            }
+        }
+    }
+
+    fn function(&self, name: &str) -> Option<&'a Function> {
+        self.bril
+            .functions
+            .iter()
+            .find(|function| function.name == name)
+    }
+
+    // Resolves the arguments of `break` to a function and an instruction index.
+    fn breakpoint(&self, args: &[&str]) -> Result<(&'a str, usize), String> {
+        let (function, target) = match args {
+            [target] => (self.stack.last().unwrap().0, *target),
+            [name, target] => {
+                let Some(name) = name.strip_prefix('@') else {
+                    return Err(format!("expected a function, got `{name}`"));
+                };
+                match self.function(name) {
+                    Some(function) => (function, *target),
+                    None => return Err(format!("no function @{name}")),
+                }
+            }
+            _ => return Err("usage: break [@f] (.label | index)".to_string()),
+        };
+
+        let index = match target.strip_prefix('.') {
+            Some(label) => function
+                .instrs
+                .iter()
+                .position(|instr| instr.label() == Some(label))
+                .ok_or_else(|| format!("no label .{label} in @{}", function.name))?,
+            None => target
+                .parse::<usize>()
+                .ok()
+                .filter(|&index| index < function.instrs.len())
+                .ok_or_else(|| format!("no instruction {target} in @{}", function.name))?,
+        };
+        Ok((&function.name, index))
+    }
+}
+
+impl<'a, R: BufRead, W: Write> Observer<'a> for Debugger<'a, R, W> {
+    fn instruction(
+        &mut self,
+        function: &'a Function,
+        index: usize,
+        env: &HashMap<&'a str, Value>,
+    ) -> Result<(), InterpError> {
+        self.stack.last_mut().unwrap().1 = index;
+        if self.detached {
+            return Ok(());
+        }
+
+        let mut reasons = Vec::new();
+        let stepped = match &mut self.steps {
+            Some(0) => true,
+            Some(n) => {
+                *n -= 1;
+                *n == 0
+            }
+            None => false,
+        };
+        if stepped {
+            self.steps = Some(0);
+        }
+        for (number, breakpoint) in self.breakpoints.iter().enumerate() {
+            if *breakpoint == Some((function.name.as_str(), index)) {
+                reasons.push(format!("breakpoint {}", number + 1));
+            }
+        }
+        let depth = self.stack.len() - 1;
+        for watchpoint in &mut self.watchpoints {
+            if watchpoint.function != function.name {
+                continue;
+            }
+            let value = env.get(watchpoint.var.as_str()).copied();
+            // A frame entered since the watchpoint was set starts from the value it has here.
+            let last = watchpoint.values.entry(depth).or_insert(value);
+            if value != *last {
+                let show = |value: Option<Value>| {
+                    value.map_or("undefined".to_string(), |value| value.to_string())
+                };
+                reasons.push(format!(
+                    "{} changed from {} to {}",
+                    watchpoint.var,
+                    show(*last),
+                    show(value)
+                ));
+                *last = value;
+            }
+        }
+
+        if !stepped && reasons.is_empty() {
+            return Ok(());
+        }
+        for reason in reasons {
+            self.write(&reason)?;
+        }
+        self.write(&self.location(function, index))?;
+        self.prompt(env)
+    }
+
+    fn call(&mut self, function: &'a Function) {
+        self.stack.push((function, 0));
+    }
+
+    fn ret(&mut self) {
+        self.stack.pop();
+        let depth = self.stack.len();
+        for watchpoint in &mut self.watchpoints {
+            watchpoint.values.remove(&depth);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::Debugger;
+    use crate::{
+        interp::{observe, Limits},
+        parser::parse,
+    };
+
+    #[test]
+    fn test_debugger() {
+        let txt = "@main {\n  i: int = const 0;\n  n: int = const 2;\n  one: int = const 1;\n.loop:\n  i: int = add i one;\n  print i;\n  done: bool = ge i n;\n  br done .end .loop;\n.end:\n  call @f i;\n}\n@f(x: int) {\n  print x;\n}\n";
+        let bril = parse(txt).unwrap();
+        let commands = "\
+break .loop
+break @f 0
+watch i
+continue
+print i n
+continue
+step 2
+delete 1
+continue
+continue
+backtrace
+print x i
+continue
+";
+        let mut out = Vec::new();
+        let mut debugger_out = Vec::new();
+        observe(
+            &bril,
+            &[],
+            &mut out,
+            Limits::default(),
+            Debugger::new(&bril, commands.as_bytes(), &mut debugger_out),
+        )
+        .unwrap();
+
+        assert_eq!(String::from_utf8(out).unwrap(), "1\n2\n2\n");
+        assert_eq!(
+            String::from_utf8(debugger_out).unwrap(),
+            "\
+@main[0]: i: int = const 0
+breakpoint 1 at @main[3]: .loop:
+breakpoint 2 at @f[0]: print x
+watching i in @main
+i changed from undefined to 0
+@main[1]: n: int = const 2
+i = 0
+n is undefined
+breakpoint 1
+@main[3]: .loop:
+i changed from 0 to 1
+@main[5]: print i
+i changed from 1 to 2
+@main[5]: print i
+breakpoint 2
+@f[0]: print x
+#0 @f[0]: print x
+#1 @main[9]: call @f i
+x = 2
+i is undefined
+"
+        );
+    }
+
+    #[test]
+    fn test_recursive_watchpoint() {
+        let txt = "@main {\n  n: int = const 2;\n  call @f n;\n}\n@f(n: int) {\n  zero: int = const 0;\n  done: bool = le n zero;\n  br done .end .rec;\n.rec:\n  one: int = const 1;\n  m: int = sub n one;\n  call @f m;\n  print n;\n.end:\n}\n";
+        let bril = parse(txt).unwrap();
+        let commands = "\
+break @f 0
+continue
+delete 1
+watch m
+continue
+continue
+backtrace
+continue
+";
+        let mut out = Vec::new();
+        let mut debugger_out = Vec::new();
+        observe(
+            &bril,
+            &[],
+            &mut out,
+            Limits::default(),
+            Debugger::new(&bril, commands.as_bytes(), &mut debugger_out),
+        )
+        .unwrap();
+
+        // Each call of @f has its own `m`: entering a call or returning from one changes nothing.
+        assert_eq!(String::from_utf8(out).unwrap(), "1\n2\n");
+        assert_eq!(
+            String::from_utf8(debugger_out).unwrap(),
+            "\
+@main[0]: n: int = const 2
+breakpoint 1 at @f[0]: zero: int = const 0
+breakpoint 1
+@f[0]: zero: int = const 0
+watching m in @f
+m changed from undefined to 1
+@f[6]: call @f m
+m changed from undefined to 0
+@f[6]: call @f m
+#0 @f[6]: call @f m
+#1 @f[6]: call @f m
+#2 @main[1]: call @f n
+"
+        );
+    }
+}
@@ -0,0 +1,175 @@
+use std::{fmt, io};
+
+use crate::{interp::InterpError, ir::Position, parser::ParseError};
+
+/// Everything that can go wrong reading, transforming or running a program.
+#[derive(Debug)]
+pub enum Error {
+    /// Reading the program or writing a result failed.
+    Io(io::Error),
+    /// The program isn't well-formed Bril JSON.
+    Json(serde_json::Error),
+    /// The program isn't well-formed Bril text.
+    Parse(ParseError),
+    UnknownOpcode {
+        function: String,
+        op: String,
+        pos: Option<Position>,
+    },
+    /// An instruction jumps to a label its function doesn't define.
+    MissingLabel {
+        function: String,
+        label: String,
+        pos: Option<Position>,
+    },
+    /// A function has no control flow graph, as when it is empty or defines a label twice.
+    InvalidCfg {
+        function: String,
+        message: String,
+    },
+    /// A transformation can't handle something a function uses, like speculation.
+    Unsupported {
+        function: String,
+        message: String,
+    },
+    UnknownFunction(String),
+    /// Type checking or verification found problems with the program.
+    Invalid {
+        when: String,
+        errors: Vec<String>,
+    },
+    Interp(InterpError),
+    /// Optimizing these benchmark programs changed what they print.
+    OutputChanged(Vec<String>),
+}
+
+/// Where a diagnostic points, printed like interpreter errors: `@f`, then `[index]` into the
+/// function's instructions if there is one, then the source position if it is known, as in
+/// `@f[3] 2:5`.
+pub(crate) struct Location<'a> {
+    pub function: &'a str,
+    pub index: Option<usize>,
+    pub pos: Option<Position>,
+}
+
+impl fmt::Display for Location<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "@{}", self.function)?;
+        if let Some(index) = self.index {
+            write!(f, "[{index}]")?;
+        }
+        if let Some(pos) = self.pos {
+            write!(f, " {pos}")?;
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fn at<'a>(function: &'a str, pos: &Option<Position>) -> Location<'a> {
+            Location {
+                function,
+                index: None,
+                pos: *pos,
+            }
+        }
+        match self {
+            Error::Io(e) => write!(f, "{e}"),
+            Error::Json(e) => write!(f, "invalid JSON: {e}"),
+            Error::Parse(e) => write!(f, "{e}"),
+            Error::UnknownOpcode { function, op, pos } => {
+                write!(f, "{}: unknown opcode `{op}`", at(function, pos))
+            }
+            Error::MissingLabel {
+                function,
+                label,
+                pos,
+            } => write!(f, "{}: label .{label} undefined", at(function, pos)),
+            Error::InvalidCfg { function, message } => write!(f, "@{function}: {message}"),
+            Error::Unsupported { function, message } => write!(f, "@{function}: {message}"),
+            Error::UnknownFunction(name) => write!(f, "no function @{name}"),
+            Error::Invalid { when, errors } => {
+                write!(f, "invalid program {when}:")?;
+                for error in errors {
+                    write!(f, "\n{error}")?;
+                }
+                Ok(())
+            }
+            Error::Interp(e) => write!(f, "{e}"),
+            Error::OutputChanged(programs) => write!(
+                f,
+                "optimization changed the output of {}",
+                programs.join(", ")
+            ),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Io(e) => Some(e),
+            Error::Json(e) => Some(e),
+            Error::Parse(e) => Some(e),
+            Error::Interp(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for Error {
+    fn from(e: io::Error) -> Self {
+        Error::Io(e)
+    }
+}
+
+impl From<serde_json::Error> for Error {
+    fn from(e: serde_json::Error) -> Self {
+        Error::Json(e)
+    }
+}
+
+impl From<ParseError> for Error {
+    fn from(e: ParseError) -> Self {
+        Error::Parse(e)
+    }
+}
+
+impl From<InterpError> for Error {
+    fn from(e: InterpError) -> Self {
+        Error::Interp(e)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{ir::Bril, parser::parse, ssa::Cfg};
+
+    #[test]
+    fn test_errors() {
+        let json = r#"{"functions": [{"name": "main", "instrs": [{"op": "frob", "pos": {"row": 2, "col": 3}}]}]}"#;
+        assert_eq!(
+            Bril::from_json(json).unwrap_err().to_string(),
+            "@main 2:3: unknown opcode `frob`"
+        );
+        assert!(Bril::from_json(r#"{"functions": [}"#)
+            .unwrap_err()
+            .to_string()
+            .starts_with("invalid JSON: "));
+
+        let cfg = |txt: &str| {
+            let bril = parse(txt).unwrap();
+            Cfg::new(&bril.functions[0]).err().unwrap().to_string()
+        };
+        assert_eq!(cfg("@main {\n}\n"), "@main: function has no instructions");
+        assert_eq!(
+            cfg("@main {\n  jmp .nowhere;\n}\n"),
+            "@main 2:3: label .nowhere undefined"
+        );
+        assert_eq!(
+            cfg("@main {\n.a:\n  nop;\n.a:\n  ret;\n}\n"),
+            "@main: label .a is defined more than once"
+        );
+    }
+}
@@ -0,0 +1,1324 @@
+use std::{
+    cmp::Ordering,
+    collections::{BTreeMap, HashMap},
+    fmt,
+    io::Write,
+};
+
+use serde::Serialize;
+
+use crate::{
+    basic_block::{partition, BasicBlock},
+    ir::{Bril, Function, Instruction, Literal, Op, Position, Type},
+};
+
+// A Rust port of `brili`: same semantics, same output and the same error messages.
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Value {
+    Int(i64),
+    Bool(bool),
+    Float(f64),
+    Char(char),
+    Pointer(Pointer),
+}
+
+/// An offset into the allocation numbered `base`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Pointer {
+    pub base: usize,
+    pub offset: i64,
+}
+
+impl Value {
+    pub(crate) fn matches(&self, r#type: &Type) -> bool {
+        matches!(
+            (self, r#type),
+            (Value::Int(_), Type::Int)
+                | (Value::Bool(_), Type::Bool)
+                | (Value::Float(_), Type::Float)
+                | (Value::Char(_), Type::Char)
+                | (Value::Pointer(_), Type::Ptr(_))
+        )
+    }
+}
+
+impl From<&Literal> for Value {
+    fn from(literal: &Literal) -> Self {
+        match *literal {
+            Literal::Int(i) => Value::Int(i),
+            Literal::Bool(b) => Value::Bool(b),
+            Literal::Float(x) => Value::Float(x),
+            Literal::Char(c) => Value::Char(c),
+        }
+    }
+}
+
+/// Formats a value the way `print` does.
+impl fmt::Display for Value {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Value::Int(i) => write!(f, "{i}"),
+            Value::Bool(b) => write!(f, "{b}"),
+            Value::Float(x) => write!(f, "{}", to_fixed_17(*x)),
+            Value::Char(c) => write!(f, "{c}"),
+            // `brili` prints pointers as the JavaScript objects they are.
+            Value::Pointer(_) => write!(f, "[object Object]"),
+        }
+    }
+}
+
+// JavaScript's `x.toFixed(17)`, except that negative zero keeps its sign like `brili` prints it.
+fn to_fixed_17(x: f64) -> String {
+    if x.is_nan() {
+        return "NaN".to_string();
+    }
+    if x.is_infinite() {
+        return if x > 0.0 { "Infinity" } else { "-Infinity" }.to_string();
+    }
+
+    let sign = if x.is_sign_negative() { "-" } else { "" };
+    let abs = x.abs();
+    if abs >= 1e21 {
+        // `toFixed` falls back to `toString` here.
+        let scientific = format!("{abs:e}");
+        let (mantissa, exponent) = scientific.split_once('e').unwrap();
+        return format!("{sign}{mantissa}e+{exponent}");
+    }
+
+    // Rust rounds exact ties to even where JavaScript rounds them up. A tie needs exactly 18
+    // fractional digits, i.e. a multiple of 2^-18 whose 18th digit is 5.
+    let long = format!("{abs:.18}");
+    if (abs * 262144.0).fract() == 0.0 && long.ends_with('5') {
+        let mut digits = long.as_bytes()[..long.len() - 1].to_vec();
+        for digit in digits.iter_mut().rev() {
+            match *digit {
+                b'.' => continue,
+                b'9' => *digit = b'0',
+                _ => {
+                    *digit += 1;
+                    return format!("{sign}{}", String::from_utf8(digits).unwrap());
+                }
+            }
+        }
+        return format!("{sign}1{}", String::from_utf8(digits).unwrap());
+    }
+
+    format!("{sign}{abs:.17}")
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct InterpError {
+    pub message: String,
+    /// The function and position of the instruction that failed, if any.
+    pub function: Option<String>,
+    pub pos: Option<Position>,
+    /// The limit the program ran into, when it was stopped rather than failing by itself.
+    pub limit: Option<Limit>,
+}
+
+impl InterpError {
+    pub(crate) fn new(message: String) -> Self {
+        Self {
+            message,
+            function: None,
+            pos: None,
+            limit: None,
+        }
+    }
+
+    pub(crate) fn exceeded(limit: Limit, message: String) -> Self {
+        Self {
+            limit: Some(limit),
+            ..Self::new(message)
+        }
+    }
+
+    // Errors keep the innermost location when they unwind through calls.
+    pub(crate) fn locate(mut self, function: &Function, instr: &Instruction) -> Self {
+        if self.function.is_none() {
+            self.function = Some(function.name.clone());
+            self.pos = instr.pos();
+        }
+        self
+    }
+}
+
+impl fmt::Display for InterpError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match (&self.function, self.pos) {
+            (Some(function), Some(pos)) => write!(f, "@{function} {pos}: {}", self.message),
+            (Some(function), None) => write!(f, "@{function}: {}", self.message),
+            (None, _) => write!(f, "{}", self.message),
+        }
+    }
+}
+
+impl std::error::Error for InterpError {}
+
+fn error<T>(message: String) -> Result<T, InterpError> {
+    Err(InterpError::new(message))
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Limit {
+    Instructions,
+    Heap,
+    CallDepth,
+}
+
+/// Resources a run may use before it is stopped with an error carrying the [`Limit`]; `None` is unlimited.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Limits {
+    /// Dynamic instructions, counted like `total_dyn_inst`.
+    pub instructions: Option<u64>,
+    /// Heap cells allocated and not yet freed.
+    pub heap: Option<usize>,
+    /// Active calls, `@main` included.
+    pub call_depth: Option<usize>,
+}
+
+/// What `brili -p` reports, plus block and edge counts when run by [`profile`].
+#[derive(Debug, Clone, PartialEq, Eq, Default, Serialize)]
+pub struct Profile {
+    pub total_dyn_inst: u64,
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    pub functions: BTreeMap<String, FunctionProfile>,
+}
+
+/// Execution counts of one function, keyed by the labels `BasicBlock::new_blocks` gives its blocks.
+#[derive(Debug, Clone, PartialEq, Eq, Default, Serialize)]
+pub struct FunctionProfile {
+    pub calls: u64,
+    pub blocks: BTreeMap<String, u64>,
+    /// Times control went from one block to another, for every edge of the CFG.
+    pub edges: BTreeMap<String, BTreeMap<String, u64>>,
+}
+
+/// Watches a program run from inside the interpreter, e.g. to stop it at breakpoints.
+pub trait Observer<'a> {
+    /// Called before `function.instrs[index]` runs, labels included, with the current frame's variables.
+    /// An error stops the program.
+    fn instruction(
+        &mut self,
+        function: &'a Function,
+        index: usize,
+        env: &HashMap<&'a str, Value>,
+    ) -> Result<(), InterpError>;
+
+    /// Called when `function` is entered, before its first instruction.
+    fn call(&mut self, _function: &'a Function) {}
+
+    /// Called when the innermost function returns.
+    fn ret(&mut self) {}
+}
+
+impl<'a, O: Observer<'a>> Observer<'a> for &mut O {
+    fn instruction(
+        &mut self,
+        function: &'a Function,
+        index: usize,
+        env: &HashMap<&'a str, Value>,
+    ) -> Result<(), InterpError> {
+        (**self).instruction(function, index, env)
+    }
+
+    fn call(&mut self, function: &'a Function) {
+        (**self).call(function)
+    }
+
+    fn ret(&mut self) {
+        (**self).ret()
+    }
+}
+
+impl<'a> Observer<'a> for () {
+    fn instruction(
+        &mut self,
+        _function: &'a Function,
+        _index: usize,
+        _env: &HashMap<&'a str, Value>,
+    ) -> Result<(), InterpError> {
+        Ok(())
+    }
+}
+
+/// Runs `@main` with command line `args`, writing whatever it prints to `out`.
+pub fn run<W: Write>(bril: &Bril, args: &[String], out: W) -> Result<Profile, InterpError> {
+    interpret(bril, args, out, Limits::default(), false, ())
+}
+
+/// Like [`run`], stopping the program once it exceeds `limits`.
+pub fn run_limited<W: Write>(
+    bril: &Bril,
+    args: &[String],
+    out: W,
+    limits: Limits,
+) -> Result<Profile, InterpError> {
+    interpret(bril, args, out, limits, false, ())
+}
+
+/// Like [`run_limited`], and also counts how often each function, block and CFG edge is executed.
+pub fn profile<W: Write>(
+    bril: &Bril,
+    args: &[String],
+    out: W,
+    limits: Limits,
+) -> Result<Profile, InterpError> {
+    interpret(bril, args, out, limits, true, ())
+}
+
+/// Like [`run_limited`], reporting every step to `observer`.
+pub fn observe<'a, W: Write, O: Observer<'a>>(
+    bril: &'a Bril,
+    args: &[String],
+    out: W,
+    limits: Limits,
+    observer: O,
+) -> Result<Profile, InterpError> {
+    interpret(bril, args, out, limits, false, observer)
+}
+
+fn interpret<'a, W: Write, O: Observer<'a>>(
+    bril: &'a Bril,
+    args: &[String],
+    out: W,
+    limits: Limits,
+    count_blocks: bool,
+    observer: O,
+) -> Result<Profile, InterpError> {
+    let mut interpreter = Interpreter {
+        bril,
+        labels: bril
+            .functions
+            .iter()
+            .map(|function| {
+                let mut labels = HashMap::new();
+                for (index, instr) in function.instrs.iter().enumerate() {
+                    if let Some(label) = instr.label() {
+                        labels.entry(label).or_insert(index);
+                    }
+                }
+                labels
+            })
+            .collect(),
+        out,
+        heap: Heap::default(),
+        icount: 0,
+        depth: 0,
+        limits,
+        blocks: count_blocks.then(|| bril.functions.iter().map(BlockCounts::new).collect()),
+        observer,
+    };
+
+    let main = find(bril, "main")?;
+    let env = main_arguments(&bril.functions[main], args)?;
+    interpreter.invoke(main, env)?;
+
+    interpreter.heap.leaks()?;
+
+    let functions = bril
+        .functions
+        .iter()
+        .zip(interpreter.blocks.into_iter().flatten())
+        .map(|(function, counts)| (function.name.clone(), counts.profile()))
+        .collect();
+
+    Ok(Profile {
+        total_dyn_inst: interpreter.icount,
+        functions,
+    })
+}
+
+// Block and edge counts of one function, with blocks numbered in `BasicBlock::new_blocks` order.
+struct BlockCounts {
+    labels: Vec<String>,
+    successors: Vec<Vec<usize>>,
+    // The block each instruction starts, if any.
+    starts: Vec<Option<usize>>,
+    calls: u64,
+    blocks: Vec<u64>,
+    edges: HashMap<(usize, usize), u64>,
+}
+
+impl BlockCounts {
+    fn new(function: &Function) -> Self {
+        let basic_blocks = BasicBlock::new_blocks(&function.instrs);
+        let labels: Vec<String> = basic_blocks
+            .iter()
+            .map(|block| block[0].label().unwrap().to_string())
+            .collect();
+        let numbers: HashMap<&str, usize> = labels
+            .iter()
+            .enumerate()
+            .map(|(number, label)| (label.as_str(), number))
+            .collect();
+        let successors = basic_blocks
+            .iter()
+            .map(|block| {
+                block
+                    .successors()
+                    .filter_map(|label| numbers.get(label.as_str()).copied())
+                    .collect()
+            })
+            .collect();
+
+        // `new_blocks` only adds a leading label and a trailing terminator to each block of `partition`.
+        let mut starts = vec![None; function.instrs.len()];
+        let mut start = 0;
+        for (number, block) in partition(&function.instrs).iter().enumerate() {
+            starts[start] = Some(number);
+            start += block.len();
+        }
+
+        Self {
+            blocks: vec![0; labels.len()],
+            labels,
+            successors,
+            starts,
+            calls: 0,
+            edges: HashMap::new(),
+        }
+    }
+
+    // Counts entering the block that instruction `i` starts, if it starts one, coming from block `from`.
+    // Returns the block `i` belongs to.
+    fn enter(&mut self, i: usize, from: Option<usize>) -> Option<usize> {
+        let Some(to) = self.starts[i] else {
+            return from;
+        };
+        self.blocks[to] += 1;
+        if let Some(from) = from {
+            *self.edges.entry((from, to)).or_insert(0) += 1;
+        }
+        Some(to)
+    }
+
+    fn profile(self) -> FunctionProfile {
+        let mut edges: BTreeMap<String, BTreeMap<String, u64>> = BTreeMap::new();
+        for (from, successors) in self.successors.iter().enumerate() {
+            for &to in successors {
+                let count = self.edges.get(&(from, to)).copied().unwrap_or(0);
+                edges
+                    .entry(self.labels[from].clone())
+                    .or_default()
+                    .insert(self.labels[to].clone(), count);
+            }
+        }
+
+        FunctionProfile {
+            calls: self.calls,
+            blocks: self.labels.iter().cloned().zip(self.blocks).collect(),
+            edges,
+        }
+    }
+}
+
+pub(crate) fn main_arguments<'a>(
+    main: &'a Function,
+    args: &[String],
+) -> Result<HashMap<&'a str, Value>, InterpError> {
+    let params = main.args.as_deref().unwrap_or_default();
+    if params.len() != args.len() {
+        return error(format!(
+            "mismatched main argument arity: expected {}; got {}",
+            params.len(),
+            args.len()
+        ));
+    }
+
+    let mut env = HashMap::new();
+    for (param, arg) in params.iter().zip(args) {
+        let value = match param.r#type {
+            Type::Int => Value::Int(parse_int(arg)?),
+            Type::Float => match arg.trim().parse::<f64>() {
+                Ok(x) if !x.is_nan() => Value::Float(x),
+                _ => {
+                    return error(format!(
+                        "float argument to main must not be 'NaN'; got {arg}"
+                    ))
+                }
+            },
+            Type::Bool => match arg.as_str() {
+                "true" => Value::Bool(true),
+                "false" => Value::Bool(false),
+                _ => {
+                    return error(format!(
+                        "boolean argument to main must be 'true'/'false'; got {arg}"
+                    ))
+                }
+            },
+            Type::Char => {
+                let mut chars = arg.chars();
+                match (chars.next(), chars.next()) {
+                    (Some(c), None) => Value::Char(c),
+                    _ => {
+                        return error(format!(
+                            "char argument to main must have one character; got {arg}"
+                        ))
+                    }
+                }
+            }
+            // `brili` leaves pointer arguments undefined.
+            Type::Ptr(_) => continue,
+        };
+        env.insert(param.name.as_str(), value);
+    }
+    Ok(env)
+}
+
+// Like JavaScript's `parseInt`, which reads the longest integer prefix.
+fn parse_int(arg: &str) -> Result<i64, InterpError> {
+    let trimmed = arg.trim_start();
+    let digits = trimmed
+        .char_indices()
+        .find(|&(i, c)| !(c.is_ascii_digit() || (i == 0 && (c == '-' || c == '+'))))
+        .map_or(trimmed, |(i, _)| &trimmed[..i]);
+    digits.parse().or_else(|_| {
+        error(format!(
+            "int argument to main must be an integer; got {arg}"
+        ))
+    })
+}
+
+pub(crate) fn arity(op: Op) -> Option<usize> {
+    match op {
+        Op::Print | Op::Ret | Op::Call | Op::Phi | Op::Const => None,
+        Op::Jmp | Op::Nop | Op::Speculate | Op::Commit => Some(0),
+        Op::Br
+        | Op::Id
+        | Op::Not
+        | Op::Alloc
+        | Op::Free
+        | Op::Load
+        | Op::Guard
+        | Op::Char2int
+        | Op::Int2char => Some(1),
+        Op::Add
+        | Op::Mul
+        | Op::Sub
+        | Op::Div
+        | Op::Eq
+        | Op::Lt
+        | Op::Gt
+        | Op::Le
+        | Op::Ge
+        | Op::And
+        | Op::Or
+        | Op::Fadd
+        | Op::Fmul
+        | Op::Fsub
+        | Op::Fdiv
+        | Op::Feq
+        | Op::Flt
+        | Op::Fle
+        | Op::Fgt
+        | Op::Fge
+        | Op::Store
+        | Op::Ptradd
+        | Op::Ceq
+        | Op::Clt
+        | Op::Cle
+        | Op::Cgt
+        | Op::Cge => Some(2),
+    }
+}
+
+// JavaScript compares strings by UTF-16 code units, which orders some characters differently than code points.
+pub(crate) fn compare_chars(a: char, b: char) -> Ordering {
+    let (mut a_units, mut b_units) = ([0; 2], [0; 2]);
+    let a = a.encode_utf16(&mut a_units);
+    let b = b.encode_utf16(&mut b_units);
+    a.cmp(&b)
+}
+
+enum Action<'a> {
+    Next,
+    Jump(&'a str),
+    Return(Option<Value>),
+}
+
+struct Frame<'a> {
+    function: &'a Function,
+    env: HashMap<&'a str, Value>,
+    // The two most recently executed labels, for `phi`.
+    last_label: Option<&'a str>,
+    current_label: Option<&'a str>,
+    // One checkpoint per enclosing `speculate`, innermost last.
+    speculation: Vec<Checkpoint<'a>>,
+}
+
+// The state `speculate` saves and a failing `guard` restores. The heap is not part of it.
+struct Checkpoint<'a> {
+    env: HashMap<&'a str, Value>,
+    last_label: Option<&'a str>,
+    current_label: Option<&'a str>,
+}
+
+impl<'a> Frame<'a> {
+    fn get(&self, var: &str) -> Result<Value, InterpError> {
+        match self.env.get(var) {
+            Some(value) => Ok(*value),
+            None => error(format!("undefined variable {var}")),
+        }
+    }
+}
+
+struct Allocation<'a> {
+    size: usize,
+    // Grown on demand up to `size`, since programs often allocate much more than they touch.
+    cells: Vec<Option<Value>>,
+    element: &'a Type,
+    // Where the allocation happened, to point at leaks.
+    function: &'a str,
+    pos: Option<Position>,
+}
+
+// Allocations are numbered in order and never reused, so a freed base stays invalid forever.
+#[derive(Default)]
+pub(crate) struct Heap<'a> {
+    allocations: BTreeMap<usize, Allocation<'a>>,
+    next: usize,
+    // Cells in all allocations.
+    size: usize,
+}
+
+impl<'a> Heap<'a> {
+    // Runs the `alloc` instruction `instr` for `size` cells, which may not take the heap past `limit`.
+    pub(crate) fn allocate(
+        &mut self,
+        instr: &'a Instruction,
+        size: i64,
+        function: &'a str,
+        limit: Option<usize>,
+    ) -> Result<Pointer, InterpError> {
+        let Some(Type::Ptr(element)) = instr.r#type() else {
+            let r#type = instr
+                .r#type()
+                .map_or("undefined".to_string(), ToString::to_string);
+            return error(format!("cannot allocate non-pointer type {type}"));
+        };
+        if size <= 0 {
+            return error(format!(
+                "must allocate a positive amount of memory: {size} <= 0"
+            ));
+        }
+        if let Some(max) = limit {
+            if self.size.saturating_add(size as usize) > max {
+                return Err(InterpError::exceeded(
+                    Limit::Heap,
+                    format!("heap limit of {max} cells exceeded"),
+                ));
+            }
+        }
+        Ok(self.alloc(size as usize, element, function, instr.pos()))
+    }
+
+    fn alloc(
+        &mut self,
+        size: usize,
+        element: &'a Type,
+        function: &'a str,
+        pos: Option<Position>,
+    ) -> Pointer {
+        let base = self.next;
+        self.next += 1;
+        self.size += size;
+        self.allocations.insert(
+            base,
+            Allocation {
+                size,
+                cells: Vec::new(),
+                element,
+                function,
+                pos,
+            },
+        );
+        Pointer { base, offset: 0 }
+    }
+
+    // Fails at the first allocation still live when the program ends.
+    pub(crate) fn leaks(&self) -> Result<(), InterpError> {
+        match self.allocations.values().next() {
+            Some(leaked) => Err(InterpError {
+                message: "Some memory locations have not been freed by end of execution."
+                    .to_string(),
+                function: Some(leaked.function.to_string()),
+                pos: leaked.pos,
+                limit: None,
+            }),
+            None => Ok(()),
+        }
+    }
+
+    pub(crate) fn free(&mut self, pointer: Pointer) -> Result<(), InterpError> {
+        let allocation = (pointer.offset == 0)
+            .then(|| self.allocations.remove(&pointer.base))
+            .flatten();
+        if let Some(allocation) = allocation {
+            self.size -= allocation.size;
+            Ok(())
+        } else {
+            error(format!(
+                "Tried to free illegal memory location base: {}, offset: {}. Offset must be 0.",
+                pointer.base, pointer.offset
+            ))
+        }
+    }
+
+    pub(crate) fn element(&self, pointer: Pointer) -> Option<&'a Type> {
+        self.allocations
+            .get(&pointer.base)
+            .map(|allocation| allocation.element)
+    }
+
+    // The allocation and index `pointer` refers to, if it is in bounds.
+    fn locate(&mut self, pointer: Pointer) -> Result<(&mut Allocation<'a>, usize), InterpError> {
+        let allocation = self.allocations.get_mut(&pointer.base);
+        match (allocation, usize::try_from(pointer.offset)) {
+            (Some(allocation), Ok(offset)) if offset < allocation.size => Ok((allocation, offset)),
+            _ => error(format!(
+                "Uninitialized heap location {} and/or illegal offset {}",
+                pointer.base, pointer.offset
+            )),
+        }
+    }
+
+    pub(crate) fn read(&mut self, pointer: Pointer) -> Result<Option<Value>, InterpError> {
+        let (allocation, offset) = self.locate(pointer)?;
+        Ok(allocation.cells.get(offset).copied().flatten())
+    }
+
+    pub(crate) fn write(&mut self, pointer: Pointer, value: Value) -> Result<(), InterpError> {
+        let (allocation, offset) = self.locate(pointer)?;
+        if allocation.cells.len() <= offset {
+            allocation.cells.resize(offset + 1, None);
+        }
+        allocation.cells[offset] = Some(value);
+        Ok(())
+    }
+}
+
+// Typed access to the arguments of one instruction.
+struct Operands<'f, 'a> {
+    op: Op,
+    args: &'a [String],
+    frame: &'f Frame<'a>,
+}
+
+impl<'f, 'a> Operands<'f, 'a> {
+    fn get(&self, index: usize) -> Result<Value, InterpError> {
+        match self.args.get(index) {
+            Some(arg) => self.frame.get(arg),
+            None => error(format!(
+                "{} expected at least {} arguments; got {}",
+                self.op,
+                index + 1,
+                self.args.len()
+            )),
+        }
+    }
+
+    fn mismatch<T>(&self, index: usize, r#type: Type) -> Result<T, InterpError> {
+        error(format!("{} argument {index} must be a {type}", self.op))
+    }
+
+    fn int(&self, index: usize) -> Result<i64, InterpError> {
+        match self.get(index)? {
+            Value::Int(i) => Ok(i),
+            _ => self.mismatch(index, Type::Int),
+        }
+    }
+
+    fn bool(&self, index: usize) -> Result<bool, InterpError> {
+        match self.get(index)? {
+            Value::Bool(b) => Ok(b),
+            _ => self.mismatch(index, Type::Bool),
+        }
+    }
+
+    fn float(&self, index: usize) -> Result<f64, InterpError> {
+        match self.get(index)? {
+            Value::Float(x) => Ok(x),
+            _ => self.mismatch(index, Type::Float),
+        }
+    }
+
+    fn char(&self, index: usize) -> Result<char, InterpError> {
+        match self.get(index)? {
+            Value::Char(c) => Ok(c),
+            _ => self.mismatch(index, Type::Char),
+        }
+    }
+
+    fn pointer(&self, index: usize) -> Result<Pointer, InterpError> {
+        match self.get(index)? {
+            Value::Pointer(pointer) => Ok(pointer),
+            _ => error(format!("{} argument {index} must be a Pointer", self.op)),
+        }
+    }
+}
+
+// The index of the only function called `name`.
+pub(crate) fn find(bril: &Bril, name: &str) -> Result<usize, InterpError> {
+    let mut found = bril
+        .functions
+        .iter()
+        .enumerate()
+        .filter(|(_, function)| function.name == name)
+        .map(|(index, _)| index);
+    match (found.next(), found.next()) {
+        (Some(index), None) => Ok(index),
+        (None, _) => error(format!("no function of name {name} found")),
+        (Some(_), Some(_)) => error(format!("multiple functions of name {name} found")),
+    }
+}
+
+pub(crate) fn label(instr: &Instruction, index: usize) -> Result<&str, InterpError> {
+    match instr.labels() {
+        [] => error(format!("missing labels; expected at least {}", index + 1)),
+        labels if labels.len() <= index => error(format!(
+            "expecting {} labels; found {}",
+            index + 1,
+            labels.len()
+        )),
+        labels => Ok(&labels[index]),
+    }
+}
+
+struct Interpreter<'a, W, O> {
+    bril: &'a Bril,
+    // The index of the first instruction carrying each label, per function.
+    labels: Vec<HashMap<&'a str, usize>>,
+    out: W,
+    heap: Heap<'a>,
+    icount: u64,
+    depth: usize,
+    limits: Limits,
+    // Per function, when profiling blocks.
+    blocks: Option<Vec<BlockCounts>>,
+    observer: O,
+}
+
+impl<'a, W: Write, O: Observer<'a>> Interpreter<'a, W, O> {
+    fn invoke(
+        &mut self,
+        index: usize,
+        env: HashMap<&'a str, Value>,
+    ) -> Result<Option<Value>, InterpError> {
+        let function = &self.bril.functions[index];
+        let mut frame = Frame {
+            function,
+            env,
+            last_label: None,
+            current_label: None,
+            speculation: Vec::new(),
+        };
+
+        if let Some(blocks) = &mut self.blocks {
+            blocks[index].calls += 1;
+        }
+        self.observer.call(function);
+
+        self.depth += 1;
+        if let Some(max) = self.limits.call_depth {
+            if self.depth > max {
+                return Err(InterpError::exceeded(
+                    Limit::CallDepth,
+                    format!("call depth limit of {max} exceeded"),
+                ));
+            }
+        }
+
+        let mut i = 0;
+        let mut block = None;
+        while let Some(instr) = function.instrs.get(i) {
+            if let Some(blocks) = &mut self.blocks {
+                block = blocks[index].enter(i, block);
+            }
+            self.observer
+                .instruction(function, i, &frame.env)
+                .map_err(|e| e.locate(function, instr))?;
+
+            if let Instruction::Label { label, .. } = instr {
+                frame.last_label = frame.current_label;
+                frame.current_label = Some(label);
+                i += 1;
+                continue;
+            }
+
+            let action = self
+                .execute(instr, &mut frame)
+                .map_err(|e| e.locate(function, instr))?;
+            match action {
+                Action::Next => i += 1,
+                Action::Jump(label) => match self.labels[index].get(label) {
+                    Some(&target) => i = target,
+                    None => {
+                        return Err(InterpError::new(format!("label {label} not found"))
+                            .locate(function, instr))
+                    }
+                },
+                Action::Return(value) => {
+                    self.depth -= 1;
+                    self.observer.ret();
+                    return Ok(value);
+                }
+            }
+        }
+
+        if !frame.speculation.is_empty() {
+            return Err(
+                InterpError::new("implicit return in speculative state".to_string())
+                    .locate(function, function.instrs.last().unwrap()),
+            );
+        }
+        self.depth -= 1;
+        self.observer.ret();
+        Ok(None)
+    }
+
+    fn execute(
+        &mut self,
+        instr: &'a Instruction,
+        frame: &mut Frame<'a>,
+    ) -> Result<Action<'a>, InterpError> {
+        self.icount += 1;
+        if let Some(max) = self.limits.instructions {
+            if self.icount > max {
+                return Err(InterpError::exceeded(
+                    Limit::Instructions,
+                    format!("instruction limit of {max} exceeded"),
+                ));
+            }
+        }
+
+        let (op, dest) = match instr {
+            Instruction::Label { .. } => return Ok(Action::Next),
+            Instruction::Constant { dest, value, .. } => {
+                frame.env.insert(dest, value.into());
+                return Ok(Action::Next);
+            }
+            Instruction::Value { op, dest, .. } => (*op, Some(dest.as_str())),
+            Instruction::Effect { op, .. } => (*op, None),
+        };
+
+        let args = instr.args();
+        if let Some(count) = arity(op) {
+            if args.len() != count {
+                return error(format!(
+                    "{op} takes {count} argument(s); got {}",
+                    args.len()
+                ));
+            }
+        }
+
+        if matches!(op, Op::Call | Op::Ret) && !frame.speculation.is_empty() {
+            return error(format!("{op} not allowed during speculation"));
+        }
+
+        let operands = Operands {
+            op,
+            args,
+            frame: &*frame,
+        };
+        let value = match op {
+            Op::Id => operands.get(0)?,
+            Op::Add => Value::Int(operands.int(0)?.wrapping_add(operands.int(1)?)),
+            Op::Mul => Value::Int(operands.int(0)?.wrapping_mul(operands.int(1)?)),
+            Op::Sub => Value::Int(operands.int(0)?.wrapping_sub(operands.int(1)?)),
+            Op::Div => {
+                let (lhs, rhs) = (operands.int(0)?, operands.int(1)?);
+                if rhs == 0 {
+                    return error("division by zero".to_string());
+                }
+                Value::Int(lhs.wrapping_div(rhs))
+            }
+            Op::Eq => Value::Bool(operands.int(0)? == operands.int(1)?),
+            Op::Lt => Value::Bool(operands.int(0)? < operands.int(1)?),
+            Op::Gt => Value::Bool(operands.int(0)? > operands.int(1)?),
+            Op::Le => Value::Bool(operands.int(0)? <= operands.int(1)?),
+            Op::Ge => Value::Bool(operands.int(0)? >= operands.int(1)?),
+            Op::Not => Value::Bool(!operands.bool(0)?),
+            Op::And => Value::Bool(operands.bool(0)? && operands.bool(1)?),
+            Op::Or => Value::Bool(operands.bool(0)? || operands.bool(1)?),
+            Op::Fadd => Value::Float(operands.float(0)? + operands.float(1)?),
+            Op::Fmul => Value::Float(operands.float(0)? * operands.float(1)?),
+            Op::Fsub => Value::Float(operands.float(0)? - operands.float(1)?),
+            Op::Fdiv => Value::Float(operands.float(0)? / operands.float(1)?),
+            Op::Feq => Value::Bool(operands.float(0)? == operands.float(1)?),
+            Op::Flt => Value::Bool(operands.float(0)? < operands.float(1)?),
+            Op::Fle => Value::Bool(operands.float(0)? <= operands.float(1)?),
+            Op::Fgt => Value::Bool(operands.float(0)? > operands.float(1)?),
+            Op::Fge => Value::Bool(operands.float(0)? >= operands.float(1)?),
+            Op::Ceq => Value::Bool(operands.char(0)? == operands.char(1)?),
+            Op::Clt => Value::Bool(compare_chars(operands.char(0)?, operands.char(1)?).is_lt()),
+            Op::Cle => Value::Bool(compare_chars(operands.char(0)?, operands.char(1)?).is_le()),
+            Op::Cgt => Value::Bool(compare_chars(operands.char(0)?, operands.char(1)?).is_gt()),
+            Op::Cge => Value::Bool(compare_chars(operands.char(0)?, operands.char(1)?).is_ge()),
+            Op::Char2int => Value::Int(operands.char(0)? as i64),
+            Op::Int2char => {
+                let i = operands.int(0)?;
+                match u32::try_from(i).ok().and_then(char::from_u32) {
+                    Some(c) => Value::Char(c),
+                    None => return error(format!("value {i} cannot be converted to char")),
+                }
+            }
+            Op::Print => {
+                let values = args
+                    .iter()
+                    .map(|arg| frame.get(arg).map(|value| value.to_string()))
+                    .collect::<Result<Vec<_>, _>>()?;
+                writeln!(self.out, "{}", values.join(" "))
+                    .or_else(|e| error(format!("cannot write output: {e}")))?;
+                return Ok(Action::Next);
+            }
+            Op::Jmp => return Ok(Action::Jump(label(instr, 0)?)),
+            Op::Br => {
+                let index = if operands.bool(0)? { 0 } else { 1 };
+                return Ok(Action::Jump(label(instr, index)?));
+            }
+            Op::Ret => {
+                return match args {
+                    [] => Ok(Action::Return(None)),
+                    [arg] => Ok(Action::Return(Some(frame.get(arg)?))),
+                    _ => error(format!("ret takes 0 or 1 argument(s); got {}", args.len())),
+                };
+            }
+            Op::Nop => return Ok(Action::Next),
+            Op::Call => {
+                self.call(instr, frame)?;
+                return Ok(Action::Next);
+            }
+            Op::Phi => {
+                self.phi(instr, frame)?;
+                return Ok(Action::Next);
+            }
+            Op::Alloc => {
+                let size = operands.int(0)?;
+                let function = frame.function.name.as_str();
+                Value::Pointer(
+                    self.heap
+                        .allocate(instr, size, function, self.limits.heap)?,
+                )
+            }
+            Op::Free => {
+                self.heap.free(operands.pointer(0)?)?;
+                return Ok(Action::Next);
+            }
+            Op::Store => {
+                let pointer = operands.pointer(0)?;
+                let value = operands.get(1)?;
+                if let Some(element) = self.heap.element(pointer) {
+                    if !value.matches(element) {
+                        return error(format!("store argument 1 must be a {element}"));
+                    }
+                }
+                self.heap.write(pointer, value)?;
+                return Ok(Action::Next);
+            }
+            Op::Load => match self.heap.read(operands.pointer(0)?)? {
+                Some(value) => value,
+                None => return error(format!("Pointer {} points to uninitialized data", args[0])),
+            },
+            Op::Ptradd => {
+                let pointer = operands.pointer(0)?;
+                Value::Pointer(Pointer {
+                    offset: pointer.offset.wrapping_add(operands.int(1)?),
+                    ..pointer
+                })
+            }
+            Op::Speculate => {
+                frame.speculation.push(Checkpoint {
+                    env: frame.env.clone(),
+                    last_label: frame.last_label,
+                    current_label: frame.current_label,
+                });
+                return Ok(Action::Next);
+            }
+            Op::Commit => {
+                if frame.speculation.is_empty() {
+                    return error("commit in non-speculative state".to_string());
+                }
+                // Like brili, one commit ends every level of speculation.
+                frame.speculation.clear();
+                return Ok(Action::Next);
+            }
+            Op::Guard => {
+                if operands.bool(0)? {
+                    return Ok(Action::Next);
+                }
+                let Some(checkpoint) = frame.speculation.pop() else {
+                    return error("abort in non-speculative state".to_string());
+                };
+                frame.env = checkpoint.env;
+                frame.last_label = checkpoint.last_label;
+                frame.current_label = checkpoint.current_label;
+                return Ok(Action::Jump(label(instr, 0)?));
+            }
+            Op::Const => unreachable!("constants are `Instruction::Constant`"),
+        };
+
+        if let Some(dest) = dest {
+            frame.env.insert(dest, value);
+        }
+        Ok(Action::Next)
+    }
+
+    fn phi(&mut self, instr: &'a Instruction, frame: &mut Frame<'a>) -> Result<(), InterpError> {
+        let (args, labels) = (instr.args(), instr.labels());
+        if args.len() != labels.len() {
+            return error("phi node has unequal numbers of labels and args".to_string());
+        }
+        let Some(last_label) = frame.last_label else {
+            return error("phi node executed with no last label".to_string());
+        };
+        let Some(dest) = instr.dest() else {
+            return Ok(());
+        };
+
+        // Coming from a label the phi doesn't mention, or from an undefined argument, leaves `dest` undefined.
+        let value = labels
+            .iter()
+            .position(|label| label == last_label)
+            .and_then(|index| frame.env.get(args[index].as_str()).copied());
+        match value {
+            Some(value) => frame.env.insert(dest, value),
+            None => frame.env.remove(dest),
+        };
+        Ok(())
+    }
+
+    fn call(&mut self, instr: &'a Instruction, frame: &mut Frame<'a>) -> Result<(), InterpError> {
+        let name = match instr.funcs() {
+            [] => return error("missing functions; expected at least 1".to_string()),
+            funcs => &funcs[0],
+        };
+        let index = find(self.bril, name)?;
+        let callee = &self.bril.functions[index];
+
+        let params = callee.args.as_deref().unwrap_or_default();
+        let args = instr.args();
+        if params.len() != args.len() {
+            return error(format!(
+                "function expected {} arguments, got {}",
+                params.len(),
+                args.len()
+            ));
+        }
+
+        let mut env = HashMap::new();
+        for (param, arg) in params.iter().zip(args) {
+            let value = frame.get(arg)?;
+            if !value.matches(&param.r#type) {
+                return error("function argument type mismatch".to_string());
+            }
+            env.insert(param.name.as_str(), value);
+        }
+
+        let value = self.invoke(index, env)?;
+
+        if let (Some(dest), Some(value)) = (instr.dest(), returned(instr, callee, value)?) {
+            frame.env.insert(dest, value);
+        }
+        Ok(())
+    }
+}
+
+// Checks what `callee` returned against the call `instr` and the callee's declaration, giving the
+// value for the call's destination.
+pub(crate) fn returned(
+    instr: &Instruction,
+    callee: &Function,
+    value: Option<Value>,
+) -> Result<Option<Value>, InterpError> {
+    let non_void = || {
+        let r#type = callee.r#type.as_ref();
+        let r#type = r#type.map_or("undefined".to_string(), ToString::to_string);
+        error(format!(
+            "non-void function (type: {type}) doesn't return anything"
+        ))
+    };
+    match (instr.dest(), instr.r#type()) {
+        (Some(_), Some(r#type)) => {
+            let Some(value) = value else {
+                return non_void();
+            };
+            if !value.matches(r#type) {
+                return error(
+                    "type of value returned by function does not match destination type"
+                        .to_string(),
+                );
+            }
+            match &callee.r#type {
+                None => error("function with void return type used in value call".to_string()),
+                Some(ret) if ret != r#type => error(
+                    "type of value returned by function does not match declaration".to_string(),
+                ),
+                Some(_) => Ok(Some(value)),
+            }
+        }
+        _ => {
+            if value.is_some() {
+                return error("unexpected value returned without destination".to_string());
+            }
+            if callee.r#type.is_some() {
+                return non_void();
+            }
+            Ok(None)
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use insta::glob;
+
+    use super::{profile, run, run_limited, to_fixed_17, Limit, Limits};
+    use crate::{bench::source_args, parser::parse};
+
+    #[test]
+    fn test_interp() {
+        glob!(
+            "..",
+            "tests/test/interp/{core,char,float,mem,mixed,spec,ssa}/*.bril",
+            |path| {
+                let txt = std::fs::read_to_string(path).unwrap();
+                let expected = std::fs::read_to_string(path.with_extension("out")).unwrap();
+
+                let mut out = Vec::new();
+                run(&parse(&txt).unwrap(), &source_args(&txt), &mut out).unwrap();
+
+                assert_eq!(String::from_utf8(out).unwrap(), expected);
+            }
+        );
+    }
+
+    #[test]
+    fn test_interp_error() {
+        glob!(
+            "..",
+            "tests/test/interp-error/{core,char,mem,spec,ssa}-error/*.bril",
+            |path| {
+                let txt = std::fs::read_to_string(path).unwrap();
+                let expected = std::fs::read_to_string(path.with_extension("err")).unwrap();
+
+                let e = run(&parse(&txt).unwrap(), &source_args(&txt), Vec::new()).unwrap_err();
+
+                assert_eq!(format!("error: {}\n", e.message), expected);
+            }
+        );
+    }
+
+    #[test]
+    fn test_profile() {
+        let txt = "@main {\n  i: int = const 0;\n  n: int = const 3;\n  one: int = const 1;\n.loop:\n  i: int = add i one;\n  done: bool = ge i n;\n  br done .end .loop;\n.end:\n  print i;\n}\n";
+        let mut out = Vec::new();
+        let profile = run(&parse(txt).unwrap(), &[], &mut out).unwrap();
+
+        assert_eq!(String::from_utf8(out).unwrap(), "3\n");
+        assert_eq!(profile.total_dyn_inst, 3 + 3 * 3 + 1);
+    }
+
+    #[test]
+    fn test_block_profile() {
+        let txt = "@main {\n  i: int = const 0;\n  n: int = const 3;\n  one: int = const 1;\n.loop:\n  i: int = add i one;\n  done: bool = ge i n;\n  br done .end .loop;\n.end:\n  print i;\n}\n";
+        let profile = profile(&parse(txt).unwrap(), &[], Vec::new(), Limits::default()).unwrap();
+
+        assert_eq!(
+            serde_json::to_value(&profile).unwrap(),
+            serde_json::json!({
+                "total_dyn_inst": 3 + 3 * 3 + 1,
+                "functions": {
+                    "main": {
+                        "calls": 1,
+                        "blocks": { "b1": 1, "loop": 3, "end": 1 },
+                        "edges": {
+                            "b1": { "loop": 1 },
+                            "loop": { "loop": 2, "end": 1 },
+                        },
+                    },
+                },
+            })
+        );
+    }
+
+    #[test]
+    fn test_limits() {
+        let looping = parse("@main {\n.top:\n  jmp .top;\n}\n").unwrap();
+        let limits = Limits {
+            instructions: Some(100),
+            ..Limits::default()
+        };
+        let e = run_limited(&looping, &[], Vec::new(), limits).unwrap_err();
+        assert_eq!(e.limit, Some(Limit::Instructions));
+        assert_eq!(
+            e.to_string(),
+            "@main 3:3: instruction limit of 100 exceeded"
+        );
+
+        let recursive = parse("@main {\n  call @main;\n}\n").unwrap();
+        let limits = Limits {
+            call_depth: Some(10),
+            ..Limits::default()
+        };
+        let e = run_limited(&recursive, &[], Vec::new(), limits).unwrap_err();
+        assert_eq!(e.limit, Some(Limit::CallDepth));
+        assert_eq!(e.to_string(), "@main 2:3: call depth limit of 10 exceeded");
+
+        let leaking = parse(
+            "@main {\n  n: int = const 10;\n.top:\n  p: ptr<int> = alloc n;\n  jmp .top;\n}\n",
+        )
+        .unwrap();
+        let limits = Limits {
+            heap: Some(25),
+            ..Limits::default()
+        };
+        let e = run_limited(&leaking, &[], Vec::new(), limits).unwrap_err();
+        assert_eq!(e.limit, Some(Limit::Heap));
+        assert_eq!(e.to_string(), "@main 4:3: heap limit of 25 cells exceeded");
+
+        // Freed cells no longer count.
+        let freeing = parse("@main {\n  n: int = const 10;\n  i: int = const 0;\n  one: int = const 1;\n.top:\n  p: ptr<int> = alloc n;\n  free p;\n  i: int = add i one;\n  done: bool = ge i n;\n  br done .end .top;\n.end:\n}\n").unwrap();
+        run_limited(&freeing, &[], Vec::new(), limits).unwrap();
+    }
+
+    #[test]
+    fn test_error_location() {
+        let txt =
+            "@main {\n  call @f;\n}\n@f {\n  zero: int = const 0;\n  x: int = div zero zero;\n}\n";
+        let e = run(&parse(txt).unwrap(), &[], Vec::new()).unwrap_err();
+
+        assert_eq!(e.to_string(), "@f 6:3: division by zero");
+    }
+
+    #[test]
+    fn test_leak_location() {
+        let txt = "@main {\n  one: int = const 1;\n  call @f one;\n}\n@f(n: int) {\n  p: ptr<int> = alloc n;\n}\n";
+        let e = run(&parse(txt).unwrap(), &[], Vec::new()).unwrap_err();
+
+        assert_eq!(
+            e.to_string(),
+            "@f 6:3: Some memory locations have not been freed by end of execution."
+        );
+    }
+
+    #[test]
+    fn test_to_fixed_17() {
+        let cases = [
+            (0.0, "0.00000000000000000"),
+            (-0.0, "-0.00000000000000000"),
+            (0.1 + 0.2, "0.30000000000000004"),
+            (-0.45, "-0.45000000000000001"),
+            (2f64.powi(-18), "0.00000381469726563"),
+            (-2f64.powi(-18), "-0.00000381469726563"),
+            (1e21, "1e+21"),
+            (f64::INFINITY, "Infinity"),
+            (f64::NAN, "NaN"),
+        ];
+
+        for (x, expected) in cases {
+            assert_eq!(to_fixed_17(x), expected);
+        }
+    }
+}
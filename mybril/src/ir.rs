@@ -0,0 +1,641 @@
+use std::{fmt, str::FromStr};
+
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+
+use crate::{error::Error, printer::python_float};
+
+/// JSON members this crate does not interpret, kept so that they survive a round-trip.
+pub type Extra = Map<String, Value>;
+
+/// A Bril program: its functions, and whatever other top-level members its JSON has.
+#[derive(Deserialize, Debug, Serialize, Clone)]
+pub struct Bril {
+    pub functions: Vec<Function>,
+    #[serde(flatten)]
+    pub extra: Extra,
+}
+
+impl Bril {
+    /// Reads a program from Bril JSON, telling an unknown opcode apart from other malformed input.
+    pub fn from_json(src: &str) -> Result<Self, Error> {
+        serde_json::from_str(src).map_err(|e| unknown_opcode(src).unwrap_or(Error::Json(e)))
+    }
+}
+
+// Tells an unknown opcode apart from other malformed JSON, which serde reports the same way.
+fn unknown_opcode(src: &str) -> Option<Error> {
+    let bril: Value = serde_json::from_str(src).ok()?;
+    for function in bril.get("functions")?.as_array()? {
+        for instr in function.get("instrs")?.as_array()? {
+            let Some(op) = instr.get("op").and_then(Value::as_str) else {
+                continue;
+            };
+            if op.parse::<Op>().is_err() {
+                return Some(Error::UnknownOpcode {
+                    function: function.get("name")?.as_str()?.to_string(),
+                    op: op.to_string(),
+                    pos: instr
+                        .get("pos")
+                        .and_then(|pos| Position::deserialize(pos).ok()),
+                });
+            }
+        }
+    }
+    None
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct Argument {
+    pub name: String,
+    pub r#type: Type,
+    #[serde(flatten)]
+    pub extra: Extra,
+}
+
+/// A function: its name, arguments, return type and instructions in program order.
+#[derive(Deserialize, Debug, Serialize, Clone)]
+pub struct Function {
+    pub instrs: Vec<Instruction>,
+    pub name: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub args: Option<Vec<Argument>>,
+    /// The return type, `None` for a function returning nothing.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub r#type: Option<Type>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub pos: Option<Position>,
+    #[serde(flatten)]
+    pub extra: Extra,
+}
+
+/// A 1-based location in the Bril text a function or instruction was parsed from.
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Position {
+    pub row: usize,
+    pub col: usize,
+}
+
+impl fmt::Display for Position {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:{}", self.row, self.col)
+    }
+}
+
+/// A primitive type is a plain string in JSON; a parameterized type is a single-key object such as `{"ptr": "int"}`.
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq, Eq, Hash)]
+#[serde(rename_all = "lowercase")]
+pub enum Type {
+    Int,
+    Bool,
+    Float,
+    Char,
+    Ptr(Box<Type>),
+}
+
+impl fmt::Display for Type {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Type::Int => write!(f, "int"),
+            Type::Bool => write!(f, "bool"),
+            Type::Float => write!(f, "float"),
+            Type::Char => write!(f, "char"),
+            Type::Ptr(inner) => write!(f, "ptr<{inner}>"),
+        }
+    }
+}
+
+macro_rules! ops {
+    ($($variant:ident => $name:literal,)*) => {
+        #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+        pub enum Op {
+            $($variant,)*
+        }
+
+        impl Op {
+            pub fn as_str(&self) -> &'static str {
+                match self {
+                    $(Op::$variant => $name,)*
+                }
+            }
+        }
+
+        impl FromStr for Op {
+            type Err = String;
+
+            fn from_str(s: &str) -> Result<Self, Self::Err> {
+                match s {
+                    $($name => Ok(Op::$variant),)*
+                    _ => Err(format!("unknown opcode `{s}`")),
+                }
+            }
+        }
+    };
+}
+
+ops! {
+    // core
+    Const => "const",
+    Add => "add",
+    Mul => "mul",
+    Sub => "sub",
+    Div => "div",
+    Eq => "eq",
+    Lt => "lt",
+    Gt => "gt",
+    Le => "le",
+    Ge => "ge",
+    Not => "not",
+    And => "and",
+    Or => "or",
+    Jmp => "jmp",
+    Br => "br",
+    Call => "call",
+    Ret => "ret",
+    Id => "id",
+    Print => "print",
+    Nop => "nop",
+    // ssa
+    Phi => "phi",
+    // float
+    Fadd => "fadd",
+    Fmul => "fmul",
+    Fsub => "fsub",
+    Fdiv => "fdiv",
+    Feq => "feq",
+    Flt => "flt",
+    Fle => "fle",
+    Fgt => "fgt",
+    Fge => "fge",
+    // memory
+    Alloc => "alloc",
+    Free => "free",
+    Store => "store",
+    Load => "load",
+    Ptradd => "ptradd",
+    // speculation
+    Speculate => "speculate",
+    Commit => "commit",
+    Guard => "guard",
+    // char
+    Ceq => "ceq",
+    Clt => "clt",
+    Cle => "cle",
+    Cgt => "cgt",
+    Cge => "cge",
+    Char2int => "char2int",
+    Int2char => "int2char",
+}
+
+impl fmt::Display for Op {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl Op {
+    /// Whether an operation producing a value does something besides, so that it has to run even
+    /// when the value is unused: a call, a load that can trap on a bad pointer, or an allocation
+    /// the program has to free.
+    pub fn has_effect(self) -> bool {
+        matches!(self, Op::Call | Op::Alloc | Op::Load)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Literal {
+    Int(i64),
+    Bool(bool),
+    Float(f64),
+    Char(char),
+}
+
+impl Literal {
+    fn from_json(r#type: &Type, value: &Value) -> Result<Self, String> {
+        let literal = match r#type {
+            Type::Int => value.as_i64().map(Literal::Int),
+            Type::Bool => value.as_bool().map(Literal::Bool),
+            // JSON has no numbers for these, so they are written as the text format prints them.
+            Type::Float => match value.as_str() {
+                Some("inf") => Some(Literal::Float(f64::INFINITY)),
+                Some("-inf") => Some(Literal::Float(f64::NEG_INFINITY)),
+                Some("nan") => Some(Literal::Float(f64::NAN)),
+                _ => value.as_f64().map(Literal::Float),
+            },
+            Type::Char => value.as_str().and_then(|s| {
+                let mut chars = s.chars();
+                match (chars.next(), chars.next()) {
+                    (Some(c), None) => Some(Literal::Char(c)),
+                    _ => None,
+                }
+            }),
+            Type::Ptr(_) => None,
+        };
+        literal.ok_or_else(|| format!("`{value}` is not a valid {type} literal"))
+    }
+
+    fn to_json(self) -> Value {
+        match self {
+            Literal::Int(i) => Value::from(i),
+            Literal::Bool(b) => Value::from(b),
+            Literal::Float(f) if !f.is_finite() => Value::from(python_float(f)),
+            Literal::Float(f) => Value::from(f),
+            Literal::Char(c) => Value::from(c.to_string()),
+        }
+    }
+}
+
+/// A label, a constant, an operation producing a value or an operation run for its effect.
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+#[serde(try_from = "RawInstruction", into = "RawInstruction")]
+pub enum Instruction {
+    Label {
+        label: String,
+        pos: Option<Position>,
+        extra: Extra,
+    },
+    Constant {
+        dest: String,
+        r#type: Type,
+        value: Literal,
+        pos: Option<Position>,
+        extra: Extra,
+    },
+    Value {
+        op: Op,
+        dest: String,
+        r#type: Type,
+        args: Vec<String>,
+        funcs: Vec<String>,
+        labels: Vec<String>,
+        pos: Option<Position>,
+        extra: Extra,
+    },
+    Effect {
+        op: Op,
+        args: Vec<String>,
+        funcs: Vec<String>,
+        labels: Vec<String>,
+        pos: Option<Position>,
+        extra: Extra,
+    },
+}
+
+impl Instruction {
+    pub fn label(&self) -> Option<&str> {
+        match self {
+            Instruction::Label { label, .. } => Some(label),
+            _ => None,
+        }
+    }
+
+    pub fn op(&self) -> Option<Op> {
+        match self {
+            Instruction::Label { .. } => None,
+            Instruction::Constant { .. } => Some(Op::Const),
+            Instruction::Value { op, .. } | Instruction::Effect { op, .. } => Some(*op),
+        }
+    }
+
+    pub fn dest(&self) -> Option<&str> {
+        match self {
+            Instruction::Constant { dest, .. } | Instruction::Value { dest, .. } => Some(dest),
+            _ => None,
+        }
+    }
+
+    pub fn dest_mut(&mut self) -> Option<&mut String> {
+        match self {
+            Instruction::Constant { dest, .. } | Instruction::Value { dest, .. } => Some(dest),
+            _ => None,
+        }
+    }
+
+    pub fn r#type(&self) -> Option<&Type> {
+        match self {
+            Instruction::Constant { r#type, .. } | Instruction::Value { r#type, .. } => {
+                Some(r#type)
+            }
+            _ => None,
+        }
+    }
+
+    pub fn args(&self) -> &[String] {
+        match self {
+            Instruction::Value { args, .. } | Instruction::Effect { args, .. } => args,
+            _ => &[],
+        }
+    }
+
+    pub fn args_mut(&mut self) -> &mut [String] {
+        match self {
+            Instruction::Value { args, .. } | Instruction::Effect { args, .. } => args,
+            _ => &mut [],
+        }
+    }
+
+    pub fn funcs(&self) -> &[String] {
+        match self {
+            Instruction::Value { funcs, .. } | Instruction::Effect { funcs, .. } => funcs,
+            _ => &[],
+        }
+    }
+
+    pub fn pos(&self) -> Option<Position> {
+        match self {
+            Instruction::Label { pos, .. }
+            | Instruction::Constant { pos, .. }
+            | Instruction::Value { pos, .. }
+            | Instruction::Effect { pos, .. } => *pos,
+        }
+    }
+
+    pub fn labels(&self) -> &[String] {
+        match self {
+            Instruction::Value { labels, .. } | Instruction::Effect { labels, .. } => labels,
+            _ => &[],
+        }
+    }
+
+    pub fn labels_mut(&mut self) -> &mut [String] {
+        match self {
+            Instruction::Value { labels, .. } | Instruction::Effect { labels, .. } => labels,
+            _ => &mut [],
+        }
+    }
+}
+
+// The JSON shape of an instruction. Every field is optional; `TryFrom` decides which kind of instruction it is.
+#[derive(Deserialize, Serialize, Default)]
+struct RawInstruction {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    label: Option<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    labels: Vec<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    op: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    r#type: Option<Type>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    dest: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    value: Option<Value>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    args: Vec<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    funcs: Vec<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pos: Option<Position>,
+    #[serde(flatten)]
+    extra: Extra,
+}
+
+impl TryFrom<RawInstruction> for Instruction {
+    type Error = String;
+
+    fn try_from(raw: RawInstruction) -> Result<Self, Self::Error> {
+        let pos = raw.pos;
+        Self::from_raw(raw).map_err(|message| match pos {
+            Some(pos) => format!("{pos}: {message}"),
+            None => message,
+        })
+    }
+}
+
+impl Instruction {
+    fn from_raw(raw: RawInstruction) -> Result<Self, String> {
+        let pos = raw.pos;
+        let extra = raw.extra;
+        if let Some(label) = raw.label {
+            return Ok(Instruction::Label { label, pos, extra });
+        }
+
+        let op: Op = raw
+            .op
+            .ok_or("instruction has neither `label` nor `op`")?
+            .parse()?;
+        if op != Op::Const && raw.value.is_some() {
+            return Err(format!("only `const` takes a value, not `{op}`"));
+        }
+
+        match (op, raw.dest) {
+            (Op::Const, Some(dest)) => {
+                let r#type = raw
+                    .r#type
+                    .ok_or_else(|| format!("constant `{dest}` has no type"))?;
+                let value = raw
+                    .value
+                    .ok_or_else(|| format!("constant `{dest}` has no value"))?;
+                let value = Literal::from_json(&r#type, &value)?;
+                Ok(Instruction::Constant {
+                    dest,
+                    r#type,
+                    value,
+                    pos,
+                    extra,
+                })
+            }
+            (Op::Const, None) => Err("constant has no destination".to_string()),
+            (op, Some(dest)) => {
+                let r#type = raw
+                    .r#type
+                    .ok_or_else(|| format!("`{op}` into `{dest}` has no type"))?;
+                Ok(Instruction::Value {
+                    op,
+                    dest,
+                    r#type,
+                    args: raw.args,
+                    funcs: raw.funcs,
+                    labels: raw.labels,
+                    pos,
+                    extra,
+                })
+            }
+            (op, None) => Ok(Instruction::Effect {
+                op,
+                args: raw.args,
+                funcs: raw.funcs,
+                labels: raw.labels,
+                pos,
+                extra,
+            }),
+        }
+    }
+}
+
+impl From<Instruction> for RawInstruction {
+    fn from(instr: Instruction) -> Self {
+        match instr {
+            Instruction::Label { label, pos, extra } => RawInstruction {
+                label: Some(label),
+                pos,
+                extra,
+                ..Default::default()
+            },
+            Instruction::Constant {
+                dest,
+                r#type,
+                value,
+                pos,
+                extra,
+            } => RawInstruction {
+                op: Some(Op::Const.to_string()),
+                r#type: Some(r#type),
+                dest: Some(dest),
+                value: Some(value.to_json()),
+                pos,
+                extra,
+                ..Default::default()
+            },
+            Instruction::Value {
+                op,
+                dest,
+                r#type,
+                args,
+                funcs,
+                labels,
+                pos,
+                extra,
+            } => RawInstruction {
+                labels,
+                op: Some(op.to_string()),
+                r#type: Some(r#type),
+                dest: Some(dest),
+                args,
+                funcs,
+                pos,
+                extra,
+                ..Default::default()
+            },
+            Instruction::Effect {
+                op,
+                args,
+                funcs,
+                labels,
+                pos,
+                extra,
+            } => RawInstruction {
+                labels,
+                op: Some(op.to_string()),
+                args,
+                funcs,
+                pos,
+                extra,
+                ..Default::default()
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use insta::glob;
+    use serde_json::Value;
+
+    use super::{Bril, Instruction, Type};
+
+    fn round_trip(src: &str) -> Value {
+        serde_json::to_value(serde_json::from_str::<Bril>(src).unwrap()).unwrap()
+    }
+
+    #[test]
+    fn test_round_trip() {
+        glob!("..", "tests/test/{parse,print}/*.json", |path| {
+            let src = std::fs::read_to_string(path).unwrap();
+            let json: Value = serde_json::from_str(&src).unwrap();
+            assert_eq!(round_trip(&src), json);
+        });
+    }
+
+    #[test]
+    fn test_pointer_types() {
+        let src = r#"{"functions": [{
+            "name": "f",
+            "args": [{"name": "p", "type": {"ptr": {"ptr": "int"}}}],
+            "type": {"ptr": "int"},
+            "instrs": [
+                {"op": "load", "dest": "q", "type": {"ptr": "int"}, "args": ["p"]},
+                {"op": "ret", "args": ["q"]}
+            ]
+        }]}"#;
+        let bril: Bril = serde_json::from_str(src).unwrap();
+        let int = || Box::new(Type::Int);
+        let function = &bril.functions[0];
+        assert_eq!(
+            function.args.as_ref().unwrap()[0].r#type,
+            Type::Ptr(Box::new(Type::Ptr(int())))
+        );
+        assert_eq!(function.r#type, Some(Type::Ptr(int())));
+        assert!(matches!(
+            &function.instrs[0],
+            Instruction::Value { r#type: Type::Ptr(inner), .. } if **inner == Type::Int
+        ));
+        assert_eq!(
+            function.to_string().lines().next(),
+            Some("@f(p: ptr<ptr<int>>): ptr<int> {")
+        );
+        assert_eq!(round_trip(src), serde_json::from_str::<Value>(src).unwrap());
+    }
+
+    #[test]
+    fn test_unknown_members() {
+        let src = r#"{
+            "functions": [{
+                "name": "ack",
+                "args": [{"name": "m", "type": "int", "note": "count"}],
+                "type": "int",
+                "attrs": {"inline": true},
+                "instrs": [
+                    {"label": "start", "note": "entry"},
+                    {"op": "const", "dest": "one", "type": "int", "value": 1, "attrs": ["hot"]},
+                    {"op": "add", "dest": "n", "type": "int", "args": ["m", "one"], "weight": 2},
+                    {"op": "ret", "args": ["n"], "attrs": {}}
+                ]
+            }, {
+                "name": "main",
+                "instrs": [{"op": "nop", "src": {"line": 1}}]
+            }],
+            "version": 2,
+            "source": "ack.bril"
+        }"#;
+        let bril: Bril = serde_json::from_str(src).unwrap();
+        assert_eq!(bril.functions[0].r#type, Some(Type::Int));
+        assert_eq!(
+            bril.functions[0].extra["attrs"]["inline"],
+            Value::Bool(true)
+        );
+        assert_eq!(
+            bril.functions[0].args.as_ref().unwrap()[0].extra["note"],
+            "count"
+        );
+        assert_eq!(bril.extra["version"], 2);
+        assert_eq!(round_trip(src), serde_json::from_str::<Value>(src).unwrap());
+    }
+
+    #[test]
+    fn test_literals() {
+        let src = r#"{"functions": [{"name": "main", "instrs": [
+            {"op": "const", "dest": "a", "type": "float", "value": "inf"},
+            {"op": "const", "dest": "b", "type": "float", "value": "-inf"},
+            {"op": "const", "dest": "c", "type": "float", "value": "nan"},
+            {"op": "const", "dest": "d", "type": "float", "value": 0.5},
+            {"op": "print", "args": ["a", "b", "c", "d"]}
+        ]}]}"#;
+        assert_eq!(round_trip(src), serde_json::from_str::<Value>(src).unwrap());
+
+        // The text format writes them the same way.
+        let txt = "@main {\n  a: float = const inf;\n  b: float = const -inf;\n  c: float = const nan;\n}\n";
+        let bril = crate::parser::parse(txt).unwrap();
+        let json = serde_json::to_string(&bril).unwrap();
+        assert_eq!(Bril::from_json(&json).unwrap().to_string(), txt);
+
+        let src = r#"{"functions": [{"name": "main", "instrs": [
+            {"op": "add", "dest": "a", "type": "int", "args": ["b", "b"], "value": 1}
+        ]}]}"#;
+        let error = Bril::from_json(src).unwrap_err().to_string();
+        assert!(
+            error.contains("only `const` takes a value, not `add`"),
+            "{error}"
+        );
+    }
+}
@@ -0,0 +1,58 @@
+//! Bril programs: reading, analyzing, optimizing and running them.
+//!
+//! - [`ir`] defines programs. [`parser::parse`] reads the text format, [`ir::Bril::from_json`] the
+//!   JSON one, and both print back with `Display` and serde.
+//! - [`basic_block`] splits functions into basic blocks, and [`ssa::Cfg`] links them into a control
+//!   flow graph.
+//! - [`dataflow`] solves dataflow problems over that graph, and [`ssa`] computes dominators and
+//!   converts functions to and from SSA form.
+//! - [`pass_manager`] runs [`pass_manager::Transform`]s with cached [`pass_manager::Analysis`]
+//!   results, like [`opt::Pass`] and [`opt::Pipeline`].
+//! - [`check`] and [`verify`] find type and structural errors, and [`interp`] and [`bytecode`] run
+//!   programs.
+
+pub mod analysis;
+pub mod basic_block;
+pub mod bench;
+pub mod bytecode;
+pub mod check;
+pub mod coverage;
+pub mod dataflow;
+pub mod debug;
+pub mod error;
+pub mod interp;
+pub mod ir;
+pub mod opt;
+pub mod parser;
+pub mod pass_manager;
+pub mod printer;
+pub mod ssa;
+pub mod stats;
+pub mod verify;
+
+#[cfg(test)]
+mod test {
+    use crate::{bench, ir::Bril};
+
+    pub fn bril2json(src: &str) -> String {
+        serde_json::to_string_pretty(&crate::parser::parse(src).unwrap()).unwrap()
+    }
+
+    pub fn bril2txt(src: &str) -> String {
+        serde_json::from_str::<Bril>(src).unwrap().to_string()
+    }
+
+    // A budget turns an optimization that makes a program loop forever into a failure.
+    pub const LIMITS: crate::interp::Limits = crate::interp::Limits {
+        instructions: Some(10_000_000),
+        heap: None,
+        call_depth: None,
+    };
+
+    // Output and dynamic instruction count of running `src` without arguments. A failing run
+    // keeps what it printed followed by the error, and counts nothing.
+    pub fn brili(src: &str) -> (String, usize) {
+        let run = bench::Run::new(&serde_json::from_str(src).unwrap(), &[], LIMITS);
+        (run.transcript(), run.result.unwrap_or(0) as usize)
+    }
+}
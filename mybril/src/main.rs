@@ -1,498 +1,376 @@
 use std::{
-    collections::{HashMap, HashSet},
-    io::{stdin, Read},
+    env, fs,
+    io::{self, stdin, stdout, Read, Write},
+    process,
+    str::FromStr,
 };
 
-use serde::{Deserialize, Serialize};
-use serde_json::Value;
-
-use crate::basic_block::{partition, BasicBlock};
-
-mod basic_block;
-mod dataflow;
-mod ssa;
-
-#[derive(Deserialize, Debug, Serialize)]
-struct Bril {
-    pub functions: Vec<Function>,
-}
+use mybril::{
+    analysis, bench, bytecode, check, coverage, debug,
+    error::Error,
+    interp,
+    ir::{Bril, Function},
+    opt::{Pass, Pipeline},
+    parser, verify,
+};
 
-#[derive(Debug, Deserialize, Serialize, Clone)]
-pub struct Argument {
-    pub name: String,
-    pub r#type: String,
+enum Format {
+    Json,
+    Text,
 }
 
-#[derive(Deserialize, Debug, Serialize)]
-pub struct Function {
-    pub instrs: Vec<Instruction>,
-    pub name: String,
-    pub args: Option<Vec<Argument>>,
+enum Verify {
+    Cfg,
+    Ssa,
 }
 
-#[derive(Deserialize, Serialize, Debug, Clone, Default)]
-pub struct Instruction {
-    #[serde(skip_serializing_if = "Option::is_none")]
-    label: Option<String>,
-    #[serde(skip_serializing_if = "Option::is_none")]
-    labels: Option<Vec<String>>,
-    #[serde(skip_serializing_if = "Option::is_none")]
-    op: Option<String>,
-    #[serde(skip_serializing_if = "Option::is_none")]
-    r#type: Option<String>,
-    #[serde(skip_serializing_if = "Option::is_none")]
-    dest: Option<String>,
-    #[serde(skip_serializing_if = "Option::is_none")]
-    value: Option<Value>,
-    #[serde(skip_serializing_if = "Option::is_none")]
-    args: Option<Vec<String>>,
+struct Options {
+    /// Print this analysis of the program instead of optimizing it, given as the first argument.
+    report: Option<analysis::Report>,
+    input_format: Format,
+    format: Format,
+    /// Passes to optimize the program with.
+    pipeline: Pipeline,
+    /// Whether `--passes` was given, so that a report is of the optimized program.
+    passes: bool,
+    /// Type check the program before optimizing it and each function after each pass.
+    check: bool,
+    /// Verify the program's structure before and after optimizing it.
+    verify: Option<Verify>,
+    /// Run the program instead of optimizing it, reporting `total_dyn_inst` on stderr like `brili -p`.
+    run: bool,
+    /// Run the program instead of optimizing it, and write its block profile as JSON to this file.
+    profile: Option<String>,
+    /// Run the program in this file under the debugger, which reads its commands from stdin.
+    debug: Option<String>,
+    /// Run the program in this file once per line of stdin, each line giving the arguments of `@main`,
+    /// and print its coverage.
+    coverage: Option<String>,
+    /// Print what each pass did to each function instead of the optimized program, with a unified
+    /// diff of each pass if `Some(true)`.
+    stats: Option<bool>,
+    /// Programs or directories of programs to run before and after optimizing them, instead of reading
+    /// one from stdin, printing a table of their dynamic instruction counts.
+    bench: Vec<String>,
+    table: bench::Table,
+    /// Arguments of `@main` when running the program, given after `--`.
+    args: Vec<String>,
+    /// Resource limits when running the program.
+    limits: interp::Limits,
 }
 
-fn main() {
-    let mut buffer = String::new();
-    stdin().read_to_string(&mut buffer).unwrap();
-    let mut bril: Bril = serde_json::from_str(&buffer).unwrap();
-
-    for function in &mut bril.functions {
-        let mut partitioned = partition(&function.instrs);
-        partitioned.iter_mut().for_each(|p| {
-            // drop_kill(p);
-            local_value_numbering(p);
-        });
-        function.instrs = partitioned.into_iter().flatten().collect();
-        /*
-        my_trivial_dce_graph(function);
-        let mut partitioned = partition(&function.instrs);
-        partitioned.iter_mut().for_each(|p| {
-            drop_kill(p);
-        });
-        function.instrs = partitioned.into_iter().flatten().collect();
-        */
+fn number<T: FromStr>(flag: &str, value: Option<String>) -> T {
+    match value.as_deref().map(str::parse) {
+        Some(Ok(n)) => n,
+        _ => {
+            eprintln!(
+                "{flag} expects a number, got {}",
+                value.as_deref().unwrap_or("nothing")
+            );
+            process::exit(2);
+        }
     }
-
-    let json_after = serde_json::to_string_pretty(&bril).unwrap();
-    print!("{json_after}");
 }
 
-fn trivial_dce(function: &mut Function) {
-    let mut used = HashSet::new();
-
-    for inst in &function.instrs {
-        if let Some(args) = inst.args.as_ref() {
-            for arg in args {
-                used.insert(arg.clone());
-            }
+fn format_flag(flag: &str, value: Option<String>) -> Format {
+    match value.as_deref() {
+        Some("json") => Format::Json,
+        Some("text") => Format::Text,
+        other => {
+            eprintln!(
+                "{flag} expects `json` or `text`, got {}",
+                other.unwrap_or("nothing")
+            );
+            process::exit(2);
         }
     }
-
-    function.instrs.retain(|inst| {
-        if let Some(dest) = inst.dest.as_ref() {
-            used.contains(dest.as_str())
-        } else {
-            true
-        }
-    });
 }
 
-fn my_trivial_dce_graph(function: &mut Function) {
-    let mut used_by_effects = HashSet::new();
-    let mut uses: HashMap<String, HashSet<String>> = HashMap::new();
-
-    for inst in &function.instrs {
-        if let Some(args) = inst.args.as_ref() {
-            if let Some(dest) = inst.dest.as_ref() {
-                uses.entry(dest.clone())
-                    .or_default()
-                    .extend(args.iter().cloned());
+fn options() -> Options {
+    let mut report = None;
+    let mut input_format = Format::Json;
+    let mut format = Format::Json;
+    let mut pipeline = Pipeline::default();
+    let mut passes = false;
+    let mut check = false;
+    let mut verify = None;
+    let mut run = false;
+    let mut profile = None;
+    let mut debug = None;
+    let mut coverage = None;
+    let mut stats = None;
+    let mut bench = Vec::new();
+    let mut table = bench::Table::Markdown;
+    let mut limits = interp::Limits::default();
+    let mut args = env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--input-format" => input_format = format_flag(&arg, args.next()),
+            "--output-format" => format = format_flag(&arg, args.next()),
+            "--passes" => {
+                passes = true;
+                let result = match args.next() {
+                    Some(spec) => pipeline.set(&spec),
+                    None => Err("--passes expects a list of passes".to_string()),
+                };
+                if let Err(e) = result {
+                    eprintln!("{e}");
+                    process::exit(2);
+                }
             }
-        }
-        if matches!(inst.op.as_deref(), Some("print") | Some("br")) {
-            used_by_effects.extend(inst.args.as_ref().unwrap().iter().cloned());
-        }
-    }
-
-    let mut used = HashSet::new();
-
-    let mut stack = used_by_effects.into_iter().collect::<Vec<_>>();
-    while let Some(dest) = stack.pop() {
-        if !used.contains(&dest) {
-            if let Some(args) = uses.get(&dest) {
-                stack.extend(args.iter().cloned());
+            "--check" => check = true,
+            "--verify" => verify = Some(Verify::Cfg),
+            "--verify-ssa" => verify = Some(Verify::Ssa),
+            "--run" => run = true,
+            "--profile" => match args.next() {
+                Some(path) => profile = Some(path),
+                None => {
+                    eprintln!("--profile expects a file name");
+                    process::exit(2);
+                }
+            },
+            "--debug" => match args.next() {
+                Some(path) => debug = Some(path),
+                None => {
+                    eprintln!("--debug expects a file name");
+                    process::exit(2);
+                }
+            },
+            "--coverage" => match args.next() {
+                Some(path) => coverage = Some(path),
+                None => {
+                    eprintln!("--coverage expects a file name");
+                    process::exit(2);
+                }
+            },
+            "--stats" => stats = Some(stats.unwrap_or(false)),
+            "--stats-diff" => stats = Some(true),
+            "--bench" => match args.next() {
+                Some(path) => bench.push(path),
+                None => {
+                    eprintln!("--bench expects a file or directory name");
+                    process::exit(2);
+                }
+            },
+            "--table" => match args.next().as_deref() {
+                Some("csv") => table = bench::Table::Csv,
+                Some("markdown") => table = bench::Table::Markdown,
+                other => {
+                    eprintln!(
+                        "--table expects `csv` or `markdown`, got {}",
+                        other.unwrap_or("nothing")
+                    );
+                    process::exit(2);
+                }
+            },
+            "--max-instructions" => {
+                limits.instructions = Some(number(&arg, args.next()));
+            }
+            "--max-heap" => limits.heap = Some(number(&arg, args.next())),
+            "--max-call-depth" => limits.call_depth = Some(number(&arg, args.next())),
+            "--" => break,
+            _ if !arg.starts_with('-') && report.is_none() => match arg.parse() {
+                Ok(analysis) => report = Some(analysis),
+                Err(e) => {
+                    eprintln!("{e}");
+                    process::exit(2);
+                }
+            },
+            _ => {
+                eprintln!("unknown argument `{arg}`");
+                process::exit(2);
             }
-            used.insert(dest);
-        }
-    }
-
-    function.instrs.retain(|inst| {
-        if let Some(dest) = inst.dest.as_ref() {
-            used.contains(dest.as_str())
-        } else {
-            true
         }
-    });
-}
-
-#[derive(Debug, Hash, PartialEq, Eq, Clone)]
-struct InstValue {
-    op: String,
-    args: Vec<usize>,
-}
-
-#[derive(Default)]
-struct ValueTable {
-    var2num: HashMap<String, usize>,
-    table: HashMap<InstValue, String>,
-    num2var: HashMap<usize, String>,
-    counter: usize,
-}
-
-impl ValueTable {
-    fn num(&mut self, var: &str) -> usize {
-        *self.var2num.entry(var.to_string()).or_insert_with(|| {
-            let num = self.counter;
-            self.counter += 1;
-            self.num2var.insert(num, var.to_string());
-            num
-        })
     }
-
-    fn root(&mut self, var: &str) -> String {
-        let num = self.num(var);
-        self.num2var[&num].clone()
+    Options {
+        report,
+        input_format,
+        format,
+        pipeline,
+        passes,
+        check,
+        verify,
+        run,
+        profile,
+        debug,
+        coverage,
+        stats,
+        bench,
+        table,
+        args: args.collect(),
+        limits,
     }
+}
 
-    fn value(
-        &mut self,
-        mut inst_value: InstValue,
-        dest: &str,
-        overwritten_after: bool,
-    ) -> (Option<String>, Option<String>) {
-        match inst_value.op.as_str() {
-            "add" => inst_value.args.sort_unstable(),
-            _ => {}
-        }
-
-        // Redifine occured. Remove old edge.
-        if let Some(&num) = self.var2num.get(dest) {
-            self.var2num.retain(|_, v| *v != num);
-        }
-
-        let old_name = dest;
-        let dest = if overwritten_after {
-            let new_dest = format!("{}_prime", dest);
-            let num = self.num(&new_dest);
-            self.var2num.insert(dest.to_string(), num);
-            new_dest
-        } else {
-            dest.to_string()
-        };
-
-        let rename = if overwritten_after {
-            Some(dest.clone())
-        } else {
-            None
+fn benchmark(options: &Options) -> Result<(), Error> {
+    let mut comparisons = Vec::new();
+    for program in bench::programs(&options.bench)? {
+        let optimize = |bril: &mut Bril| {
+            let program = options.check.then(|| bril.clone());
+            options
+                .pipeline
+                .run_with(bril, check_passes(program.as_ref()))?;
+            validate(bril, options, "after optimization")
         };
-
-        if inst_value.op == "id" {
-            let arg = inst_value.args[0];
-            self.var2num.insert(dest.to_string(), arg);
-            return (Some(self.num2var[&arg].clone()), rename);
-        }
-
-        if let Some(var) = self.table.get(&inst_value) {
-            self.var2num.insert(dest.to_string(), self.var2num[var]);
-            if overwritten_after {
-                self.var2num.insert(old_name.to_string(), self.var2num[var]);
-            }
-            (Some(var.clone()), rename)
-        } else {
-            if !overwritten_after {
-                let num = self.counter;
-                self.counter += 1;
-                self.num2var.insert(num, dest.to_string());
-                self.var2num.insert(dest.to_string(), num);
-            }
-            self.table.insert(inst_value.clone(), dest.to_string());
-
-            (None, rename)
-        }
+        comparisons.push(bench::Comparison::new(&program, optimize, options.limits)?);
+    }
+    write!(
+        stdout().lock(),
+        "{}",
+        bench::table(&comparisons, options.table)
+    )?;
+
+    let changed = comparisons
+        .iter()
+        .filter(|comparison| !comparison.matches())
+        .map(|comparison| comparison.name.clone())
+        .collect::<Vec<_>>();
+    if changed.is_empty() {
+        Ok(())
+    } else {
+        Err(Error::OutputChanged(changed))
     }
 }
 
-fn local_value_numbering(instrs: &mut [Instruction]) {
-    let mut table = ValueTable::default();
-
-    let mut dest_map = HashMap::new();
-    for (i, inst) in instrs.iter().enumerate() {
-        if let Some(dest) = inst.dest.as_deref() {
-            dest_map.insert(dest.to_string(), i);
-        }
+fn validate(bril: &Bril, options: &Options, when: &str) -> Result<(), Error> {
+    let mut errors = Vec::new();
+    if options.check {
+        errors.extend(check::check(bril).iter().map(ToString::to_string));
     }
-
-    for (i, inst) in instrs.iter_mut().enumerate() {
-        if inst.args.is_none() {
-            continue;
-        }
-
-        if let Some(dest) = inst.dest.as_deref() {
-            let inst_value = InstValue {
-                op: inst.op.as_ref().unwrap().clone(),
-                args: inst
-                    .args
-                    .as_ref()
-                    .unwrap()
-                    .iter()
-                    .map(|arg| table.num(arg))
-                    .collect(),
-            };
-
-            let (alias, rename) = table.value(inst_value, dest, dest_map[dest] > i);
-            if let Some(rename) = rename {
-                inst.dest = Some(rename);
-            }
-
-            if let Some(alias) = alias {
-                *inst = Instruction {
-                    dest: inst.dest.clone(),
-                    op: Some("id".to_string()),
-                    args: Some(vec![alias]),
-                    ..Default::default()
-                };
-                continue;
-            }
+    match options.verify {
+        Some(Verify::Cfg) => errors.extend(verify::verify(bril).iter().map(ToString::to_string)),
+        Some(Verify::Ssa) => {
+            errors.extend(verify::verify_ssa(bril).iter().map(ToString::to_string))
         }
+        None => {}
+    }
 
-        inst.args = inst
-            .args
-            .as_ref()
-            .map(|args| args.iter().map(|arg| table.root(&arg)).collect::<Vec<_>>());
+    if errors.is_empty() {
+        Ok(())
+    } else {
+        Err(Error::Invalid {
+            when: when.to_string(),
+            errors,
+        })
     }
 }
 
-fn drop_kill(instrs: &mut Vec<Instruction>) {
-    let mut unused = HashMap::new();
-    let mut kill = HashSet::new();
-
-    for (i, inst) in instrs.iter().enumerate() {
-        if let Some(args) = inst.args.as_ref() {
-            for arg in args {
-                unused.remove(arg);
-            }
-        }
-        if let Some(dest) = inst.dest.as_ref() {
-            if let Some(old) = unused.insert(dest, i) {
-                kill.insert(old);
-            }
+// Type checks each function after each pass, naming the pass that made it ill-typed, when given the
+// program as it was before, which has the signatures passes leave alone.
+fn check_passes(program: Option<&Bril>) -> impl FnMut(&Pass, &Function) -> Result<(), Error> + '_ {
+    move |pass, function| {
+        let Some(program) = program else {
+            return Ok(());
+        };
+        let errors = check::check_function(program, function);
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(Error::Invalid {
+                when: format!("after {pass} on @{}", function.name),
+                errors: errors.iter().map(ToString::to_string).collect(),
+            })
         }
     }
-
-    *instrs = instrs
-        .into_iter()
-        .enumerate()
-        .filter_map(|(i, inst)| {
-            if kill.contains(&i) {
-                None
-            } else {
-                Some(inst.clone())
-            }
-        })
-        .collect();
 }
 
-fn dot(bril: &Bril) -> String {
-    use std::fmt::Write;
-
-    let mut dot = String::new();
-
-    for function in &bril.functions {
-        writeln!(dot, "digraph {} {{", function.name).unwrap();
-
-        let basic_blocks = BasicBlock::new_blocks(&function.instrs);
-
-        for block in &basic_blocks {
-            let label = block[0].label.as_deref().unwrap();
-            writeln!(dot, "  {label};").unwrap();
-        }
-
-        for block in &basic_blocks {
-            let from = block[0].label.as_deref().unwrap();
-            if let Some(labels) = block.last().unwrap().labels.as_ref() {
-                for to in labels {
-                    writeln!(dot, "  {} -> {};", from, to).unwrap();
-                }
-            }
-        }
-
-        writeln!(dot, "}}").unwrap();
+fn main() {
+    let options = options();
+    if let Err(e) = run(&options) {
+        eprintln!("error: {e}");
+        // 3 for a program that ran into a resource limit, so that harnesses can tell it apart from a failure.
+        let code = match e {
+            Error::Interp(e) if e.limit.is_some() => 3,
+            _ => 1,
+        };
+        process::exit(code);
     }
-
-    dot
 }
 
-#[cfg(test)]
-mod test {
+fn run(options: &Options) -> Result<(), Error> {
+    if !options.bench.is_empty() {
+        return benchmark(options);
+    }
 
-    use insta::{assert_display_snapshot, glob};
-    use std::{
-        io::Write,
-        process::{Command, Stdio},
+    let buffer = match options.debug.as_ref().or(options.coverage.as_ref()) {
+        Some(path) => fs::read_to_string(path)?,
+        None => {
+            let mut buffer = String::new();
+            stdin().read_to_string(&mut buffer)?;
+            buffer
+        }
+    };
+    let mut bril = match options.input_format {
+        Format::Json => Bril::from_json(&buffer)?,
+        Format::Text => parser::parse(&buffer)?,
     };
 
-    use super::*;
-
-    pub fn bril2json(src: &str) -> String {
-        let mut child = Command::new("bril2json")
-            .stdin(Stdio::piped())
-            .stdout(Stdio::piped())
-            .spawn()
-            .unwrap();
-        let mut stdin = child.stdin.take().unwrap();
-        stdin.write_all(src.as_bytes()).unwrap();
-        drop(stdin);
+    validate(&bril, options, "in input")?;
 
-        String::from_utf8(child.wait_with_output().unwrap().stdout).unwrap()
+    if options.debug.is_some() {
+        let debugger = debug::Debugger::new(&bril, stdin().lock(), stdout());
+        interp::observe(&bril, &options.args, stdout(), options.limits, debugger)?;
+        return Ok(());
     }
 
-    pub fn bril2txt(src: &str) -> String {
-        let mut child = Command::new("bril2txt")
-            .stdin(Stdio::piped())
-            .stdout(Stdio::piped())
-            .spawn()
-            .unwrap();
-        let mut stdin = child.stdin.take().unwrap();
-        stdin.write_all(src.as_bytes()).unwrap();
-        drop(stdin);
-
-        String::from_utf8(child.wait_with_output().unwrap().stdout).unwrap()
+    if options.coverage.is_some() {
+        let mut coverage = coverage::Coverage::new(&bril);
+        for line in stdin().lines() {
+            let args = line?
+                .split_whitespace()
+                .map(str::to_string)
+                .collect::<Vec<_>>();
+            // A failing run still covers what it executed.
+            if let Err(e) = interp::observe(&bril, &args, io::sink(), options.limits, &mut coverage)
+            {
+                eprintln!("error: {e}");
+            }
+        }
+        write!(stdout().lock(), "{coverage}")?;
+        return Ok(());
     }
 
-    pub fn brili(src: &str) -> (String, usize) {
-        let mut child = Command::new("brili")
-            .arg("-p")
-            .stdin(Stdio::piped())
-            .stdout(Stdio::piped())
-            .stderr(Stdio::piped())
-            .spawn()
-            .unwrap();
-        let mut stdin = child.stdin.take().unwrap();
-        stdin.write_all(src.as_bytes()).unwrap();
-        drop(stdin);
-
-        let result = child.wait_with_output().unwrap();
-        let stdout = String::from_utf8(result.stdout).unwrap();
-        let stderr = String::from_utf8(result.stderr).unwrap();
-
-        (
-            stdout,
-            stderr
-                .trim_start_matches("total_dyn_inst: ")
-                .trim()
-                .parse()
-                .unwrap_or(114514),
-        )
+    if options.run {
+        let profile = bytecode::run(&bril, &options.args, stdout().lock(), options.limits)?;
+        eprintln!("total_dyn_inst: {}", profile.total_dyn_inst);
+        return Ok(());
     }
 
-    #[test]
-    fn test_dot() {
-        glob!("..", "tests/test/interp/core/*.bril", |path| {
-            let txt = std::fs::read_to_string(&path).unwrap();
-            let json = bril2json(&txt);
-            let bril: Bril = serde_json::from_str(&json).unwrap();
-            let dot = dot(&bril);
-            assert_display_snapshot!(dot);
-        });
+    if let Some(path) = &options.profile {
+        let profile = interp::profile(&bril, &options.args, stdout().lock(), options.limits)?;
+        fs::write(path, serde_json::to_string_pretty(&profile)?)?;
+        return Ok(());
     }
 
-    #[test]
-    fn test_trivial_dce() {
-        glob!("..", "tests/examples/tdce/*.bril", |path| {
-            let txt = std::fs::read_to_string(&path).unwrap();
-            let json = bril2json(&txt);
-            let mut bril: Bril = serde_json::from_str(&json).unwrap();
-
-            for function in &mut bril.functions {
-                trivial_dce(function);
-            }
-
-            let json_after = serde_json::to_string_pretty(&bril).unwrap();
-
-            let orig = brili(&json);
-            let after = brili(&json_after);
-
-            assert_eq!(orig.0, after.0);
-            assert!(orig.1 >= after.1);
-
-            assert_display_snapshot!(format!(
-                "{}\n\n{} -> {}\n\n{}",
-                txt,
-                orig.1,
-                after.1,
-                bril2txt(json_after.as_str())
-            ));
-        });
+    let program = options.check.then(|| bril.clone());
+    if let Some(diff) = options.stats {
+        let stats = options
+            .pipeline
+            .stats(&mut bril, diff, check_passes(program.as_ref()))?;
+        validate(&bril, options, "after optimization")?;
+        write!(stdout().lock(), "{stats}")?;
+        return Ok(());
     }
 
-    #[test]
-    fn test_my_trivial_dce_graph() {
-        glob!("..", "tests/examples/tdce/*.bril", |path| {
-            let txt = std::fs::read_to_string(&path).unwrap();
-            let json = bril2json(&txt);
-            let mut bril: Bril = serde_json::from_str(&json).unwrap();
-
-            for function in &mut bril.functions {
-                my_trivial_dce_graph(function);
-            }
-
-            let json_after = serde_json::to_string_pretty(&bril).unwrap();
-
-            let orig = brili(&json);
-            let after = brili(&json_after);
-
-            assert_eq!(orig.0, after.0);
-            assert!(orig.1 >= after.1);
-
-            assert_display_snapshot!(format!(
-                "{}\n\n{} -> {}\n\n{}",
-                txt,
-                orig.1,
-                after.1,
-                bril2txt(json_after.as_str())
-            ));
-        });
+    if let Some(report) = options.report {
+        if options.passes {
+            options
+                .pipeline
+                .run_with(&mut bril, check_passes(program.as_ref()))?;
+        }
+        write!(stdout().lock(), "{}", analysis::report(&bril, report)?)?;
+        return Ok(());
     }
 
-    #[test]
-    fn test_lvn() {
-        glob!("..", "tests/examples/lvn/*.bril", |path| {
-            let txt = std::fs::read_to_string(&path).unwrap();
-            let json = bril2json(&txt);
-            let mut bril: Bril = serde_json::from_str(&json).unwrap();
-
-            for function in &mut bril.functions {
-                let mut partition = partition(&function.instrs);
-                partition.iter_mut().for_each(|p| {
-                    local_value_numbering(p);
-                    drop_kill(p)
-                });
-                function.instrs = partition.into_iter().flatten().collect();
-                my_trivial_dce_graph(function);
-            }
-
-            let json_after = serde_json::to_string_pretty(&bril).unwrap();
-
-            let orig = brili(&json);
-            let after = brili(&json_after);
+    options
+        .pipeline
+        .run_with(&mut bril, check_passes(program.as_ref()))?;
 
-            assert_eq!(orig.0, after.0);
-            assert!(orig.1 >= after.1);
+    validate(&bril, options, "after optimization")?;
 
-            assert_display_snapshot!(format!(
-                "{}\n\n{} -> {}\n\n{}",
-                txt,
-                orig.1,
-                after.1,
-                bril2txt(json_after.as_str())
-            ));
-        });
+    match options.format {
+        Format::Json => write!(stdout().lock(), "{}", serde_json::to_string_pretty(&bril)?)?,
+        Format::Text => write!(stdout().lock(), "{bril}")?,
     }
+    Ok(())
 }
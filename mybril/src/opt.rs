@@ -0,0 +1,863 @@
+use std::{
+    collections::{HashMap, HashSet},
+    fmt,
+    str::FromStr,
+};
+
+use crate::{
+    basic_block::partition,
+    dataflow::{Constant, Constants},
+    error::Error,
+    ir::{Bril, Function, Instruction, Literal, Op},
+    pass_manager::{AnalysisManager, PassManager, Preserved, Transform},
+    ssa::{self, Cfg},
+    stats,
+};
+
+/// A transformation of one function, named on the command line.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Pass {
+    /// Local value numbering, per basic block.
+    Lvn,
+    /// Drops instructions whose destination is never used anywhere.
+    Tdce,
+    /// Drops instructions that no effect depends on.
+    DceGraph,
+    /// Drops assignments overwritten before use, per basic block.
+    DropKill,
+    /// Global constant propagation and folding.
+    Cprop,
+    /// Converts to SSA form.
+    Ssa,
+    /// Converts out of SSA form.
+    FromSsa,
+}
+
+const PASSES: &[(&str, Pass)] = &[
+    ("lvn", Pass::Lvn),
+    ("tdce", Pass::Tdce),
+    ("dce-graph", Pass::DceGraph),
+    ("drop-kill", Pass::DropKill),
+    ("cprop", Pass::Cprop),
+    ("ssa", Pass::Ssa),
+    ("from-ssa", Pass::FromSsa),
+];
+
+impl FromStr for Pass {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match PASSES.iter().find(|(name, _)| *name == s) {
+            Some(&(_, pass)) => Ok(pass),
+            None => {
+                let names = PASSES.iter().map(|(name, _)| *name).collect::<Vec<_>>();
+                Err(format!(
+                    "unknown pass `{s}`; expected one of {}",
+                    names.join(", ")
+                ))
+            }
+        }
+    }
+}
+
+impl fmt::Display for Pass {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let (name, _) = PASSES.iter().find(|(_, pass)| pass == self).unwrap();
+        write!(f, "{name}")
+    }
+}
+
+impl Transform for Pass {
+    fn run(
+        &self,
+        function: &mut Function,
+        analyses: &mut AnalysisManager,
+    ) -> Result<Preserved, Error> {
+        // Dead code elimination can empty a block, which then disappears from the graph.
+        let blocks = matches!(self, Pass::Tdce | Pass::DceGraph | Pass::DropKill)
+            .then(|| block_labels(function));
+        match self {
+            Pass::Lvn => per_block(function, |block| local_value_numbering(block)),
+            Pass::Tdce => trivial_dce(function),
+            Pass::DceGraph => my_trivial_dce_graph(function),
+            Pass::DropKill => per_block(function, drop_kill),
+            Pass::Cprop => {
+                if constant_propagation(function, analyses)? {
+                    return Ok(Preserved::none());
+                }
+            }
+            Pass::Ssa => {
+                ssa::to_ssa(function, analyses)?;
+                return Ok(Preserved::none());
+            }
+            Pass::FromSsa => {
+                ssa::from_ssa(function, analyses)?;
+                return Ok(Preserved::none());
+            }
+        }
+        if blocks.is_some_and(|blocks| blocks != block_labels(function)) {
+            return Ok(Preserved::none());
+        }
+        // Otherwise the passes only dropped or rewrote instructions that aren't labels or jumps, or,
+        // for `cprop`, didn't turn a branch into a jump.
+        Ok(Preserved::control_flow())
+    }
+}
+
+// The label of each block of `function`, `None` for a block the graph labels itself.
+fn block_labels(function: &Function) -> Vec<Option<String>> {
+    partition(&function.instrs)
+        .iter()
+        .map(|block| block[0].label().map(str::to_string))
+        .collect()
+}
+
+/// The passes to run, in order, on every function unless it has a pipeline of its own.
+pub struct Pipeline {
+    passes: PassManager<Pass>,
+    functions: HashMap<String, PassManager<Pass>>,
+}
+
+impl Default for Pipeline {
+    fn default() -> Self {
+        Self {
+            passes: PassManager::new(vec![Pass::Lvn]),
+            functions: HashMap::new(),
+        }
+    }
+}
+
+impl Pipeline {
+    /// Sets the pipeline from a `--passes` value: comma-separated pass names, prefixed with `@f=`
+    /// to apply to function `f` only. An empty list runs nothing.
+    pub fn set(&mut self, spec: &str) -> Result<(), String> {
+        let (function, list) = match spec.strip_prefix('@') {
+            Some(rest) => match rest.split_once('=') {
+                Some((function, list)) => (Some(function), list),
+                None => return Err(format!("expected `@function=passes`, got `{spec}`")),
+            },
+            None => (None, spec),
+        };
+        let passes = list
+            .split(',')
+            .filter(|name| !name.is_empty())
+            .map(str::parse)
+            .collect::<Result<Vec<Pass>, _>>()?;
+        let passes = PassManager::new(passes);
+
+        match function {
+            Some(function) => {
+                self.functions.insert(function.to_string(), passes);
+            }
+            None => self.passes = passes,
+        }
+        Ok(())
+    }
+
+    /// Runs the pipeline on every function of `bril`.
+    pub fn run(&self, bril: &mut Bril) -> Result<(), Error> {
+        self.run_with(bril, |_, _| Ok(()))
+    }
+
+    /// Runs the pipeline, showing `after` each pass with the function it left, as to check it. An
+    /// error from `after` stops the run.
+    pub fn run_with(
+        &self,
+        bril: &mut Bril,
+        mut after: impl FnMut(&Pass, &Function) -> Result<(), Error>,
+    ) -> Result<(), Error> {
+        self.check(bril)?;
+        for function in &mut bril.functions {
+            self.passes(&function.name).run_with(function, &mut after)?;
+        }
+        Ok(())
+    }
+
+    /// Runs the pipeline like [`Pipeline::run_with`], collecting what each pass did to each
+    /// function, with a diff if `diff`.
+    pub fn stats(
+        &self,
+        bril: &mut Bril,
+        diff: bool,
+        mut after: impl FnMut(&Pass, &Function) -> Result<(), Error>,
+    ) -> Result<stats::Stats, Error> {
+        self.check(bril)?;
+        let mut functions = Vec::new();
+        for function in &mut bril.functions {
+            let mut before = function.clone();
+            let mut passes = Vec::new();
+            self.passes(&function.name)
+                .run_with(function, |pass, function| {
+                    passes.push(stats::PassStats::new(
+                        pass.to_string(),
+                        &before,
+                        function,
+                        diff,
+                    ));
+                    before = function.clone();
+                    after(pass, function)
+                })?;
+            functions.push((function.name.clone(), passes));
+        }
+        Ok(stats::Stats { functions })
+    }
+
+    fn passes(&self, function: &str) -> &PassManager<Pass> {
+        self.functions.get(function).unwrap_or(&self.passes)
+    }
+
+    // Every function with a pipeline of its own must exist.
+    fn check(&self, bril: &Bril) -> Result<(), Error> {
+        for name in self.functions.keys() {
+            if !bril.functions.iter().any(|function| &function.name == name) {
+                return Err(Error::UnknownFunction(name.clone()));
+            }
+        }
+        Ok(())
+    }
+}
+
+// Runs a local pass on each basic block, also split after each `speculate`, so that no pass sees
+// the variables a failing `guard` rolls back on both sides.
+fn per_block(function: &mut Function, pass: impl FnMut(&mut Vec<Instruction>)) {
+    let mut partitioned = partition(&function.instrs)
+        .iter()
+        .flat_map(|block| block.split_inclusive(|instr| instr.op() == Some(Op::Speculate)))
+        .map(<[_]>::to_vec)
+        .collect::<Vec<_>>();
+    partitioned.iter_mut().for_each(pass);
+    function.instrs = partitioned.into_iter().flatten().collect();
+}
+
+/// Replaces instructions whose value is known in every run with a `const`, and branches on a known
+/// condition with a jump. Bril arguments can only be variables, so other uses of a known variable,
+/// like `print` or an `add` with an unknown operand, keep it as it is. Returns whether it replaced a
+/// branch, changing the control flow graph.
+pub fn constant_propagation(
+    function: &mut Function,
+    analyses: &mut AnalysisManager,
+) -> Result<bool, Error> {
+    if function.instrs.is_empty() {
+        return Ok(false);
+    }
+    ssa::no_speculation(function, "constant propagation")?;
+    let constants = analyses.get::<Constants>(function)?;
+    let cfg = analyses.get::<Cfg>(function)?;
+
+    let mut jumps = false;
+    let mut blocks = partition(&function.instrs);
+    // The graph's blocks are the partition's, labeled.
+    for (block, labeled) in blocks.iter_mut().zip(cfg.blocks()) {
+        let mut known = constants.0[labeled[0].label().unwrap()].0.clone();
+        for instr in block {
+            let constant = Constant::of(instr, &known);
+            match (&mut *instr, constant) {
+                (
+                    Instruction::Value {
+                        dest,
+                        r#type,
+                        pos,
+                        extra,
+                        ..
+                    },
+                    Constant::Value(value),
+                ) => {
+                    *instr = Instruction::Constant {
+                        dest: dest.clone(),
+                        r#type: r#type.clone(),
+                        value,
+                        pos: *pos,
+                        extra: extra.clone(),
+                    };
+                }
+                (
+                    Instruction::Effect {
+                        op: op @ Op::Br,
+                        args,
+                        labels,
+                        ..
+                    },
+                    _,
+                ) => {
+                    if let Some(&Constant::Value(Literal::Bool(taken))) = known.get(&args[0]) {
+                        *op = Op::Jmp;
+                        args.clear();
+                        *labels = vec![labels[if taken { 0 } else { 1 }].clone()];
+                        jumps = true;
+                    }
+                }
+                _ => {}
+            }
+            if let Some(dest) = instr.dest() {
+                known.insert(dest.to_string(), constant);
+            }
+        }
+    }
+    function.instrs = blocks.into_iter().flatten().collect();
+    Ok(jumps)
+}
+
+/// Drops instructions whose destination no instruction of the function uses, once.
+pub fn trivial_dce(function: &mut Function) {
+    let mut used = HashSet::new();
+
+    for inst in &function.instrs {
+        for arg in inst.args() {
+            used.insert(arg.clone());
+        }
+    }
+
+    function.instrs.retain(|inst| {
+        if let Some(dest) = inst.dest() {
+            used.contains(dest) || inst.op().is_some_and(Op::has_effect)
+        } else {
+            true
+        }
+    });
+}
+
+/// Drops instructions that no effect depends on, directly or through other instructions.
+pub fn my_trivial_dce_graph(function: &mut Function) {
+    let mut used_by_effects = HashSet::new();
+    let mut uses: HashMap<String, HashSet<String>> = HashMap::new();
+
+    for inst in &function.instrs {
+        match inst {
+            Instruction::Value { op, args, .. } if op.has_effect() => {
+                used_by_effects.extend(args.iter().cloned());
+            }
+            Instruction::Value { dest, args, .. } => {
+                uses.entry(dest.clone())
+                    .or_default()
+                    .extend(args.iter().cloned());
+            }
+            Instruction::Effect { args, .. } => {
+                used_by_effects.extend(args.iter().cloned());
+            }
+            _ => {}
+        }
+    }
+
+    let mut used = HashSet::new();
+
+    let mut stack = used_by_effects.into_iter().collect::<Vec<_>>();
+    while let Some(dest) = stack.pop() {
+        if !used.contains(&dest) {
+            if let Some(args) = uses.get(&dest) {
+                stack.extend(args.iter().cloned());
+            }
+            used.insert(dest);
+        }
+    }
+
+    function.instrs.retain(|inst| {
+        if let Some(dest) = inst.dest() {
+            used.contains(dest) || inst.op().is_some_and(Op::has_effect)
+        } else {
+            true
+        }
+    });
+}
+
+#[derive(Debug, Hash, PartialEq, Eq, Clone)]
+struct InstValue {
+    op: Op,
+    args: Vec<usize>,
+    funcs: Vec<String>,
+    labels: Vec<String>,
+}
+
+// Operations whose value depends on more than their operands: what a call returns, a new
+// allocation, what memory holds when loaded, or which edge a `phi` came in by.
+fn reusable(op: Op) -> bool {
+    !matches!(op, Op::Call | Op::Alloc | Op::Load | Op::Phi)
+}
+
+#[derive(Default)]
+struct ValueTable {
+    // The number of the value each variable of the original block has.
+    var2num: HashMap<String, usize>,
+    table: HashMap<InstValue, usize>,
+    // The variables of the rewritten block holding each value, oldest first, and the other way round.
+    holders: HashMap<usize, Vec<String>>,
+    held: HashMap<String, usize>,
+    counter: usize,
+}
+
+impl ValueTable {
+    fn fresh(&mut self) -> usize {
+        self.counter += 1;
+        self.counter - 1
+    }
+
+    // A variable read before the block defines it holds a value of its own.
+    fn num(&mut self, var: &str) -> usize {
+        if let Some(&num) = self.var2num.get(var) {
+            return num;
+        }
+        let num = self.fresh();
+        self.assign(var, var, num);
+        num
+    }
+
+    fn holder(&self, num: usize) -> Option<&String> {
+        self.holders.get(&num).and_then(|holders| holders.first())
+    }
+
+    fn root(&mut self, var: &str) -> String {
+        let num = self.num(var);
+        self.holder(num).map_or(var, String::as_str).to_string()
+    }
+
+    // `var` of the original block now has value `num`, which `dest` of the rewritten block holds.
+    // Whatever `dest` held before is gone.
+    fn assign(&mut self, var: &str, dest: &str, num: usize) {
+        if let Some(old) = self.held.insert(dest.to_string(), num) {
+            self.holders
+                .get_mut(&old)
+                .unwrap()
+                .retain(|holder| holder != dest);
+        }
+        self.holders.entry(num).or_default().push(dest.to_string());
+        self.var2num.insert(var.to_string(), num);
+    }
+}
+
+/// Local value numbering of one basic block: reuses values computed before, copying them with `id`,
+/// and renames destinations overwritten later in the block to `x_prime`.
+pub fn local_value_numbering(instrs: &mut [Instruction]) {
+    let mut table = ValueTable::default();
+
+    let mut dest_map = HashMap::new();
+    for (i, inst) in instrs.iter().enumerate() {
+        if let Some(dest) = inst.dest() {
+            dest_map.insert(dest.to_string(), i);
+        }
+    }
+
+    for (i, inst) in instrs.iter_mut().enumerate() {
+        let nums = inst
+            .args()
+            .iter()
+            .map(|arg| table.num(arg))
+            .collect::<Vec<_>>();
+        for arg in inst.args_mut() {
+            *arg = table.root(arg);
+        }
+
+        match inst {
+            Instruction::Value {
+                op,
+                dest,
+                args,
+                funcs,
+                labels,
+                ..
+            } => {
+                let var = dest.clone();
+                if dest_map[&var] > i {
+                    *dest = format!("{var}_prime");
+                }
+
+                let mut inst_value = InstValue {
+                    op: *op,
+                    args: nums,
+                    funcs: funcs.clone(),
+                    labels: labels.clone(),
+                };
+                if inst_value.op == Op::Add {
+                    inst_value.args.sort_unstable();
+                }
+                let found = if inst_value.op == Op::Id {
+                    Some(inst_value.args[0])
+                } else if reusable(inst_value.op) {
+                    table.table.get(&inst_value).copied()
+                } else {
+                    None
+                };
+                // A value whose variables were all overwritten has to be computed again.
+                let num = match found.and_then(|num| Some((num, table.holder(num)?.clone()))) {
+                    Some((num, holder)) => {
+                        *op = Op::Id;
+                        *args = vec![holder];
+                        funcs.clear();
+                        labels.clear();
+                        num
+                    }
+                    None => {
+                        let num = table.fresh();
+                        if reusable(inst_value.op) {
+                            table.table.insert(inst_value, num);
+                        }
+                        num
+                    }
+                };
+                table.assign(&var, dest, num);
+            }
+            Instruction::Constant { dest, .. } => {
+                let num = table.fresh();
+                table.assign(dest, dest, num);
+            }
+            _ => {}
+        }
+    }
+}
+
+/// Drops assignments in one basic block that are overwritten before they are used.
+pub fn drop_kill(instrs: &mut Vec<Instruction>) {
+    let mut unused = HashMap::new();
+    let mut kill = HashSet::new();
+
+    for (i, inst) in instrs.iter().enumerate() {
+        for arg in inst.args() {
+            unused.remove(arg.as_str());
+        }
+        if let Some(dest) = inst.dest() {
+            if let Some(old) = unused.insert(dest, i) {
+                if !instrs[old].op().is_some_and(Op::has_effect) {
+                    kill.insert(old);
+                }
+            }
+        }
+    }
+
+    *instrs = instrs
+        .iter()
+        .enumerate()
+        .filter_map(|(i, inst)| {
+            if kill.contains(&i) {
+                None
+            } else {
+                Some(inst.clone())
+            }
+        })
+        .collect();
+}
+
+#[cfg(test)]
+mod test {
+    use insta::{assert_snapshot, glob};
+
+    use super::*;
+    use crate::{bench, check, test::LIMITS};
+
+    // Runs the example at `path` before and after `optimize`, checking that it still prints the same
+    // and doesn't run longer, and describes the change for a snapshot.
+    fn optimized(path: &std::path::Path, optimize: impl Fn(&mut Function)) -> String {
+        let txt = std::fs::read_to_string(path).unwrap();
+        let program = bench::Program {
+            name: path.display().to_string(),
+            bril: crate::parser::parse(&txt).unwrap(),
+            args: Vec::new(),
+        };
+        let mut after = None;
+        let optimize = |bril: &mut Bril| {
+            bril.functions.iter_mut().for_each(&optimize);
+            let errors = check::check(bril);
+            assert!(errors.is_empty(), "{errors:?}");
+            after = Some(bril.clone());
+            Ok(())
+        };
+        let comparison = bench::Comparison::new(&program, optimize, LIMITS).unwrap();
+        assert!(comparison.matches(), "{comparison:?}");
+
+        // A failing run counts nothing.
+        let count = |run: &bench::Run| *run.result.as_ref().unwrap_or(&0);
+        let (before, after_count) = (count(&comparison.before), count(&comparison.after));
+        assert!(before >= after_count);
+
+        format!("{txt}\n\n{before} -> {after_count}\n\n{}", after.unwrap())
+    }
+
+    #[test]
+    fn test_trivial_dce() {
+        glob!("..", "tests/examples/tdce/*.bril", |path| {
+            assert_snapshot!(optimized(path, trivial_dce));
+        });
+    }
+
+    #[test]
+    fn test_my_trivial_dce_graph() {
+        glob!("..", "tests/examples/tdce/*.bril", |path| {
+            assert_snapshot!(optimized(path, my_trivial_dce_graph));
+        });
+    }
+
+    #[test]
+    fn test_lvn() {
+        glob!("..", "tests/examples/lvn/*.bril", |path| {
+            assert_snapshot!(optimized(path, |function| {
+                per_block(function, |block| {
+                    local_value_numbering(block);
+                    drop_kill(block)
+                });
+                my_trivial_dce_graph(function);
+            }));
+        });
+    }
+
+    #[test]
+    fn test_lvn_memory() {
+        glob!("..", "tests/test/interp/mem/*.bril", |path| {
+            assert_snapshot!(optimized(path, |function| {
+                per_block(function, |block| {
+                    local_value_numbering(block);
+                    drop_kill(block)
+                });
+                my_trivial_dce_graph(function);
+            }));
+        });
+    }
+
+    #[test]
+    fn test_speculation() {
+        glob!("..", "tests/test/interp/spec/*.bril", |path| {
+            assert_snapshot!(optimized(path, |function| {
+                per_block(function, |block| {
+                    local_value_numbering(block);
+                    drop_kill(block)
+                });
+                my_trivial_dce_graph(function);
+            }));
+        });
+
+        let path = concat!(
+            env!("CARGO_MANIFEST_DIR"),
+            "/tests/test/interp/spec/spec-nested.bril"
+        );
+        let mut bril = crate::parser::parse(&std::fs::read_to_string(path).unwrap()).unwrap();
+        let mut pipeline = Pipeline::default();
+        pipeline.set("ssa,from-ssa").unwrap();
+        assert_eq!(
+            pipeline.run(&mut bril).unwrap_err().to_string(),
+            "@main: SSA conversion doesn't support speculation"
+        );
+    }
+
+    #[test]
+    fn test_constant_propagation() {
+        glob!("..", "tests/examples/{df,lvn,tdce}/*.bril", |path| {
+            assert_snapshot!(optimized(path, |function| {
+                constant_propagation(function, &mut AnalysisManager::default()).unwrap();
+                my_trivial_dce_graph(function);
+            }));
+        });
+
+        let txt = "@main(n: int) {\n  one: int = const 1;\n  zero: int = const 0;\n  two: int = add one one;\n  big: bool = gt two one;\n  br big .yes .no;\n.yes:\n  x: int = add n two;\n  print x;\n  ret;\n.no:\n  n: int = const 3;\n  z: int = div one zero;\n  print n z;\n}\n";
+        let mut bril = crate::parser::parse(txt).unwrap();
+        let changed =
+            constant_propagation(&mut bril.functions[0], &mut AnalysisManager::default()).unwrap();
+        assert!(changed);
+        assert_eq!(
+            bril.to_string(),
+            "@main(n: int) {\n  one: int = const 1;\n  zero: int = const 0;\n  two: int = const 2;\n  big: bool = const true;\n  jmp .yes;\n.yes:\n  x: int = add n two;\n  print x;\n  ret;\n.no:\n  n: int = const 3;\n  z: int = div one zero;\n  print n z;\n}\n"
+        );
+
+        // Uses fold in later blocks too, but only instructions that become constants change.
+        let txt = "@main(n: int) {\n  one: int = const 1;\n  jmp .next;\n.next:\n  x: int = id one;\n  y: int = add n one;\n  print one x y;\n}\n";
+        let mut bril = crate::parser::parse(txt).unwrap();
+        let changed =
+            constant_propagation(&mut bril.functions[0], &mut AnalysisManager::default()).unwrap();
+        assert!(!changed);
+        assert_eq!(
+            bril.to_string(),
+            txt.replace("x: int = id one", "x: int = const 1")
+        );
+
+        // Bril JSON has no literal for infinity, so `inf` stays a division.
+        let txt = "@main {\n  one: float = const 1.0;\n  zero: float = const 0.0;\n  inf: float = fdiv one zero;\n  print inf;\n}\n";
+        let mut bril = crate::parser::parse(txt).unwrap();
+        constant_propagation(&mut bril.functions[0], &mut AnalysisManager::default()).unwrap();
+        assert_eq!(
+            bril.to_string(),
+            crate::parser::parse(txt).unwrap().to_string()
+        );
+
+        let txt = "@main {\n  b: bool = const true;\n  br b .a;\n.a:\n}\n";
+        let mut bril = crate::parser::parse(txt).unwrap();
+        let result = constant_propagation(&mut bril.functions[0], &mut AnalysisManager::default());
+        assert_eq!(
+            result.unwrap_err().to_string(),
+            "@main: `br b .a` should have one argument and two labels"
+        );
+    }
+
+    #[test]
+    fn test_lvn_redefinitions() {
+        let lvn = |txt: &str| {
+            let mut bril = crate::parser::parse(txt).unwrap();
+            local_value_numbering(&mut bril.functions[0].instrs);
+            bril.to_string()
+        };
+        assert_eq!(
+            lvn("@main {\n  a: int = const 4;\n  b: int = add a a;\n  b: int = add a a;\n  print b;\n}\n"),
+            "@main {\n  a: int = const 4;\n  b_prime: int = add a a;\n  b: int = id b_prime;\n  print b_prime;\n}\n"
+        );
+        // The second `a` is a new value, and `c` keeps the one `a` had before.
+        assert_eq!(
+            lvn("@main(a: int) {\n  c: int = id a;\n  b: int = add a a;\n  a: int = const 2;\n  d: int = add a a;\n  print b c d;\n}\n"),
+            "@main(a: int) {\n  c: int = id a;\n  b: int = add a a;\n  a: int = const 2;\n  d: int = add a a;\n  print b c d;\n}\n"
+        );
+    }
+
+    #[test]
+    fn test_calls() {
+        let txt = "@main {\n  x: int = const 1;\n  a: int = call @f x;\n  b: int = call @g x;\n  c: int = call @f x;\n  print a b;\n  d: int = call @f x;\n  d: int = const 0;\n  print d;\n}\n@f(x: int): int {\n  print x;\n  ret x;\n}\n@g(x: int): int {\n  y: int = add x x;\n  ret y;\n}\n";
+        let mut bril = crate::parser::parse(txt).unwrap();
+        let mut pipeline = Pipeline::default();
+        pipeline.set("lvn,drop-kill,tdce,dce-graph").unwrap();
+        pipeline.run(&mut bril).unwrap();
+        assert_eq!(
+            bril.to_string(),
+            txt.replace("d: int = call", "d_prime: int = call")
+        );
+    }
+
+    #[test]
+    fn test_loads() {
+        // The load traps after the `free`, so it has to stay even though `x` is unused.
+        let txt = "@main {\n  one: int = const 1;\n  p: ptr<int> = alloc one;\n  free p;\n  x: int = load p;\n  x: int = const 0;\n  print x;\n}\n";
+        let mut bril = crate::parser::parse(txt).unwrap();
+        let mut pipeline = Pipeline::default();
+        pipeline.set("drop-kill,tdce,dce-graph").unwrap();
+        pipeline.run(&mut bril).unwrap();
+        assert_eq!(bril.to_string(), txt);
+    }
+
+    #[test]
+    fn test_lvn_keeps_positions() {
+        let txt = "@main {\n  a: int = const 4;\n  b: int = add a a;\n  c: int = add a a;\n  print c;\n}\n";
+        let mut bril = crate::parser::parse(txt).unwrap();
+
+        local_value_numbering(&mut bril.functions[0].instrs);
+
+        let c = &bril.functions[0].instrs[2];
+        assert_eq!(c.to_string(), "c: int = id b");
+        assert_eq!(c.pos(), Some(crate::ir::Position { row: 4, col: 3 }));
+    }
+
+    // A pass, or a check that the cached dominators are those of the function as it is.
+    enum Step {
+        Pass(Pass),
+        Dominators,
+    }
+
+    impl Transform for Step {
+        fn run(
+            &self,
+            function: &mut Function,
+            analyses: &mut AnalysisManager,
+        ) -> Result<Preserved, Error> {
+            match self {
+                Step::Pass(pass) => pass.run(function, analyses),
+                Step::Dominators => {
+                    let dominators = analyses.get::<ssa::Dominators>(function)?;
+                    assert_eq!(dominators.0, Cfg::new(function)?.dominators());
+                    Ok(Preserved::all())
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_preserved() {
+        // Dead code elimination empties the block after the `guard`, so `.end` comes right after it.
+        let txt = "@main(c: bool) {\n  speculate;\n  guard c .end;\n  b: int = const 2;\n.end:\n  print c;\n}\n";
+        for pass in [Pass::Tdce, Pass::DceGraph] {
+            let mut bril = crate::parser::parse(txt).unwrap();
+            let steps = vec![Step::Dominators, Step::Pass(pass), Step::Dominators];
+            PassManager::new(steps).run(&mut bril.functions[0]).unwrap();
+            assert_eq!(bril.functions[0].instrs.len(), 4);
+        }
+    }
+
+    #[test]
+    fn test_pipeline() {
+        let txt = "@main {\n  a: int = const 4;\n  b: int = add a a;\n  c: int = add a a;\n  print c;\n}\n@f {\n  a: int = const 4;\n  a: int = const 5;\n  print a;\n}\n";
+        let mut bril = crate::parser::parse(txt).unwrap();
+
+        let mut pipeline = Pipeline::default();
+        pipeline.set("lvn,dce-graph").unwrap();
+        pipeline.set("@f=").unwrap();
+        pipeline.run(&mut bril).unwrap();
+        assert_eq!(
+            bril.to_string(),
+            "@main {\n  a: int = const 4;\n  b: int = add a a;\n  print b;\n}\n@f {\n  a: int = const 4;\n  a: int = const 5;\n  print a;\n}\n"
+        );
+
+        pipeline.set("@f=ssa,tdce,from-ssa").unwrap();
+        pipeline.run(&mut bril).unwrap();
+        assert_eq!(
+            bril.functions[1].to_string(),
+            "@f {\n.b1:\n  a.2: int = const 5;\n  print a.2;\n  ret;\n}\n"
+        );
+
+        assert_eq!(
+            pipeline.set("lvn,gvn").unwrap_err(),
+            "unknown pass `gvn`; expected one of lvn, tdce, dce-graph, drop-kill, cprop, ssa, from-ssa"
+        );
+        pipeline.set("@g=lvn").unwrap();
+        assert_eq!(
+            pipeline.run(&mut bril).unwrap_err().to_string(),
+            "no function @g"
+        );
+    }
+
+    #[test]
+    fn test_run_with() {
+        let txt = "@main {\n  a: int = const 4;\n  b: int = add a a;\n  c: int = add a a;\n  print c;\n}\n";
+        let mut bril = crate::parser::parse(txt).unwrap();
+
+        let mut pipeline = Pipeline::default();
+        pipeline.set("lvn,dce-graph").unwrap();
+        let program = bril.clone();
+        let mut ran = Vec::new();
+        let result = pipeline.run_with(&mut bril, |pass, function| {
+            ran.push(pass.to_string());
+            let errors = check::check_function(&program, function);
+            assert!(errors.is_empty(), "{errors:?}");
+            Err(Error::UnknownFunction(function.name.clone()))
+        });
+        assert!(matches!(result, Err(Error::UnknownFunction(_))));
+        assert_eq!(ran, ["lvn"]);
+    }
+
+    #[test]
+    fn test_stats() {
+        let txt = "@main {\n  a: int = const 4;\n  b: int = add a a;\n  c: int = add a a;\n  print c;\n}\n";
+        let mut bril = crate::parser::parse(txt).unwrap();
+
+        let mut pipeline = Pipeline::default();
+        pipeline.set("lvn,dce-graph").unwrap();
+        let stats = pipeline.stats(&mut bril, true, |_, _| Ok(())).unwrap();
+        assert_eq!(
+            stats.to_string(),
+            "\
+@main:
+  pass       before  after  id  renamed  removed  added
+  lvn             4      4   1        0        0      0
+  dce-graph       4      3   0        0        1      0
+--- @main before lvn
++++ @main after lvn
+@@ -1,6 +1,6 @@
+ @main {
+   a: int = const 4;
+   b: int = add a a;
+-  c: int = add a a;
+-  print c;
++  c: int = id b;
++  print b;
+ }
+--- @main before dce-graph
++++ @main after dce-graph
+@@ -1,6 +1,5 @@
+ @main {
+   a: int = const 4;
+   b: int = add a a;
+-  c: int = id b;
+   print b;
+ }
+"
+        );
+    }
+}
@@ -0,0 +1,479 @@
+use std::fmt;
+
+use crate::ir::{Argument, Bril, Extra, Function, Instruction, Literal, Op, Position, Type};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseError {
+    pub line: usize,
+    pub col: usize,
+    pub message: String,
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:{}: {}", self.line, self.col, self.message)
+    }
+}
+
+impl std::error::Error for ParseError {}
+
+/// Parses a program in the Bril text format, the same language `bril2json` accepts.
+pub fn parse(src: &str) -> Result<Bril, ParseError> {
+    let mut parser = Parser::new(src);
+    let mut functions = Vec::new();
+
+    loop {
+        parser.skip_trivia();
+        if parser.peek().is_none() {
+            break;
+        }
+        functions.push(parser.function()?);
+    }
+
+    Ok(Bril {
+        functions,
+        extra: Extra::new(),
+    })
+}
+
+struct Parser {
+    chars: Vec<char>,
+    index: usize,
+    line: usize,
+    col: usize,
+}
+
+impl Parser {
+    fn new(src: &str) -> Self {
+        Self {
+            chars: src.chars().collect(),
+            index: 0,
+            line: 1,
+            col: 1,
+        }
+    }
+
+    fn peek(&self) -> Option<char> {
+        self.chars.get(self.index).copied()
+    }
+
+    fn bump(&mut self) -> Option<char> {
+        let c = self.peek()?;
+        self.index += 1;
+        if c == '\n' {
+            self.line += 1;
+            self.col = 1;
+        } else {
+            self.col += 1;
+        }
+        Some(c)
+    }
+
+    fn position(&self) -> Position {
+        Position {
+            row: self.line,
+            col: self.col,
+        }
+    }
+
+    fn error_at<T>(&self, pos: Position, message: String) -> Result<T, ParseError> {
+        Err(ParseError {
+            line: pos.row,
+            col: pos.col,
+            message,
+        })
+    }
+
+    fn error<T>(&self, message: String) -> Result<T, ParseError> {
+        self.error_at(self.position(), message)
+    }
+
+    fn describe_next(&self) -> String {
+        match self.peek() {
+            Some(c) => format!("`{c}`"),
+            None => "end of input".to_string(),
+        }
+    }
+
+    // Skips whitespace and `#` comments.
+    fn skip_trivia(&mut self) {
+        while let Some(c) = self.peek() {
+            if c == '#' {
+                while !matches!(self.peek(), None | Some('\n')) {
+                    self.bump();
+                }
+            } else if c.is_whitespace() {
+                self.bump();
+            } else {
+                break;
+            }
+        }
+    }
+
+    fn eat(&mut self, expected: char) -> bool {
+        self.skip_trivia();
+        if self.peek() == Some(expected) {
+            self.bump();
+            true
+        } else {
+            false
+        }
+    }
+
+    fn expect(&mut self, expected: char) -> Result<(), ParseError> {
+        if self.eat(expected) {
+            Ok(())
+        } else {
+            self.error(format!(
+                "expected `{expected}`, found {}",
+                self.describe_next()
+            ))
+        }
+    }
+
+    fn ident(&mut self) -> Result<String, ParseError> {
+        self.skip_trivia();
+        match self.peek() {
+            Some(c) if c == '_' || c == '%' || c.is_ascii_alphabetic() => {}
+            _ => {
+                return self.error(format!(
+                    "expected an identifier, found {}",
+                    self.describe_next()
+                ))
+            }
+        }
+
+        let mut ident = String::new();
+        while let Some(c) = self.peek() {
+            if c == '_' || c == '%' || c == '.' || c.is_ascii_alphanumeric() {
+                ident.push(c);
+                self.bump();
+            } else {
+                break;
+            }
+        }
+        Ok(ident)
+    }
+
+    fn r#type(&mut self) -> Result<Type, ParseError> {
+        self.skip_trivia();
+        let start = self.position();
+        let name = self.ident()?;
+
+        if self.eat('<') {
+            let inner = self.r#type()?;
+            self.expect('>')?;
+            match name.as_str() {
+                "ptr" => Ok(Type::Ptr(Box::new(inner))),
+                _ => self.error_at(start, format!("unknown parameterized type `{name}`")),
+            }
+        } else {
+            match name.as_str() {
+                "int" => Ok(Type::Int),
+                "bool" => Ok(Type::Bool),
+                "float" => Ok(Type::Float),
+                "char" => Ok(Type::Char),
+                _ => self.error_at(start, format!("unknown type `{name}`")),
+            }
+        }
+    }
+
+    fn function(&mut self) -> Result<Function, ParseError> {
+        self.skip_trivia();
+        let pos = self.position();
+        self.expect('@')?;
+        let name = self.ident()?;
+
+        let mut args = Vec::new();
+        if self.eat('(') && !self.eat(')') {
+            loop {
+                let name = self.ident()?;
+                self.expect(':')?;
+                let r#type = self.r#type()?;
+                args.push(Argument {
+                    name,
+                    r#type,
+                    extra: Extra::new(),
+                });
+                if self.eat(')') {
+                    break;
+                }
+                self.expect(',')?;
+            }
+        }
+
+        let r#type = if self.eat(':') {
+            Some(self.r#type()?)
+        } else {
+            None
+        };
+
+        self.expect('{')?;
+        let mut instrs = Vec::new();
+        while !self.eat('}') {
+            if self.peek().is_none() {
+                return self.error(format!("function `{name}` is missing a closing `}}`"));
+            }
+            instrs.push(self.instruction()?);
+        }
+
+        Ok(Function {
+            instrs,
+            name,
+            args: if args.is_empty() { None } else { Some(args) },
+            r#type,
+            pos: Some(pos),
+            extra: Extra::new(),
+        })
+    }
+
+    fn instruction(&mut self) -> Result<Instruction, ParseError> {
+        self.skip_trivia();
+        let start = self.position();
+
+        if self.eat('.') {
+            let label = self.ident()?;
+            self.expect(':')?;
+            return Ok(Instruction::Label {
+                label,
+                pos: Some(start),
+                extra: Extra::new(),
+            });
+        }
+
+        let first = self.ident()?;
+
+        if self.eat(':') {
+            let r#type = self.r#type()?;
+            self.expect('=')?;
+            self.value(first, r#type, start)
+        } else if self.eat('=') {
+            self.error_at(start, format!("`{first}` has no type annotation"))
+        } else {
+            let op = self.op(&first, start)?;
+            let (args, funcs, labels) = self.operands()?;
+            Ok(Instruction::Effect {
+                op,
+                args,
+                funcs,
+                labels,
+                pos: Some(start),
+                extra: Extra::new(),
+            })
+        }
+    }
+
+    // The right-hand side of `dest: type = ...;`.
+    fn value(
+        &mut self,
+        dest: String,
+        r#type: Type,
+        pos: Position,
+    ) -> Result<Instruction, ParseError> {
+        self.skip_trivia();
+        let start = self.position();
+        let name = self.ident()?;
+        let op = self.op(&name, start)?;
+
+        if op == Op::Const {
+            let value = self.literal(&r#type)?;
+            self.expect(';')?;
+            Ok(Instruction::Constant {
+                dest,
+                r#type,
+                value,
+                pos: Some(pos),
+                extra: Extra::new(),
+            })
+        } else {
+            let (args, funcs, labels) = self.operands()?;
+            Ok(Instruction::Value {
+                op,
+                dest,
+                r#type,
+                args,
+                funcs,
+                labels,
+                pos: Some(pos),
+                extra: Extra::new(),
+            })
+        }
+    }
+
+    fn op(&self, name: &str, start: Position) -> Result<Op, ParseError> {
+        name.parse()
+            .or_else(|message| self.error_at(start, message))
+    }
+
+    // Arguments, `@functions` and `.labels` up to and including the closing `;`.
+    #[allow(clippy::type_complexity)]
+    fn operands(&mut self) -> Result<(Vec<String>, Vec<String>, Vec<String>), ParseError> {
+        let mut args = Vec::new();
+        let mut funcs = Vec::new();
+        let mut labels = Vec::new();
+
+        loop {
+            if self.eat(';') {
+                return Ok((args, funcs, labels));
+            } else if self.eat('@') {
+                funcs.push(self.ident()?);
+            } else if self.eat('.') {
+                labels.push(self.ident()?);
+            } else if self.peek().is_none() {
+                return self.error("expected `;`, found end of input".to_string());
+            } else {
+                args.push(self.ident()?);
+            }
+        }
+    }
+
+    fn literal(&mut self, r#type: &Type) -> Result<Literal, ParseError> {
+        self.skip_trivia();
+        let start = self.position();
+
+        if let Type::Char = r#type {
+            return self.char_literal();
+        }
+
+        let mut token = String::new();
+        while let Some(c) = self.peek() {
+            if c.is_whitespace() || c == ';' || c == '#' {
+                break;
+            }
+            token.push(c);
+            self.bump();
+        }
+
+        let literal = match r#type {
+            Type::Int => token.parse().ok().map(Literal::Int),
+            Type::Bool => match token.as_str() {
+                "true" => Some(Literal::Bool(true)),
+                "false" => Some(Literal::Bool(false)),
+                _ => None,
+            },
+            Type::Float => token.parse().ok().map(Literal::Float),
+            Type::Char | Type::Ptr(_) => None,
+        };
+
+        literal.map_or_else(
+            || self.error_at(start, format!("`{token}` is not a valid {type} literal")),
+            Ok,
+        )
+    }
+
+    fn char_literal(&mut self) -> Result<Literal, ParseError> {
+        let start = self.position();
+        if !self.eat('\'') {
+            return self.error(format!(
+                "expected a character literal, found {}",
+                self.describe_next()
+            ));
+        }
+
+        let c = match self.bump() {
+            Some('\\') => match self.bump() {
+                Some('0') => '\0',
+                Some('a') => '\u{07}',
+                Some('b') => '\u{08}',
+                Some('t') => '\t',
+                Some('n') => '\n',
+                Some('v') => '\u{0b}',
+                Some('f') => '\u{0c}',
+                Some('r') => '\r',
+                _ => {
+                    return self.error_at(start, "unknown escape in character literal".to_string())
+                }
+            },
+            Some(c) if c != '\'' && c != '\n' => c,
+            _ => return self.error_at(start, "empty character literal".to_string()),
+        };
+
+        if self.bump() != Some('\'') {
+            return self.error_at(
+                start,
+                "character literal must hold exactly one character".to_string(),
+            );
+        }
+        Ok(Literal::Char(c))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use insta::glob;
+    use serde_json::Value;
+
+    use super::parse;
+
+    fn strip_positions(value: &mut Value) {
+        match value {
+            Value::Object(map) => {
+                map.remove("pos");
+                map.values_mut().for_each(strip_positions);
+            }
+            Value::Array(values) => values.iter_mut().for_each(strip_positions),
+            _ => {}
+        }
+    }
+
+    #[test]
+    fn test_parse() {
+        glob!("..", "tests/test/parse/*.bril", |path| {
+            let txt = std::fs::read_to_string(path).unwrap();
+            let expected = std::fs::read_to_string(path.with_extension("json")).unwrap();
+            let expected: Value = serde_json::from_str(&expected).unwrap();
+
+            let mut actual = serde_json::to_value(parse(&txt).unwrap()).unwrap();
+            // Like `bril2json`, the fixtures only carry positions when run with `-p`.
+            if !txt.starts_with("# ARGS: -p") {
+                strip_positions(&mut actual);
+            }
+            assert_eq!(actual, expected);
+        });
+    }
+
+    #[test]
+    fn test_parse_corpus() {
+        glob!(
+            "..",
+            "tests/{examples,test/interp,test/print}/**/*.bril",
+            |path| {
+                let txt = std::fs::read_to_string(path).unwrap();
+                if let Err(e) = parse(&txt) {
+                    panic!("{}: {e}", path.display());
+                }
+            }
+        );
+    }
+
+    #[test]
+    fn test_parse_error() {
+        let cases = [
+            (
+                "@main {\n  v: int = const 1\n}",
+                "3:1: expected `;`, found `}`",
+            ),
+            (
+                "@main {\n  v: int = frob a b;\n}",
+                "2:12: unknown opcode `frob`",
+            ),
+            (
+                "@main {\n  v: ptr<int> = const 2;\n}",
+                "2:23: `2` is not a valid ptr<int> literal",
+            ),
+            (
+                "@main {\n  v = const 2;\n}",
+                "2:3: `v` has no type annotation",
+            ),
+            ("@main(a: i32) {\n}", "1:10: unknown type `i32`"),
+            (
+                "@main {\n  print v;\n",
+                "3:1: function `main` is missing a closing `}`",
+            ),
+        ];
+
+        for (src, message) in cases {
+            assert_eq!(parse(src).unwrap_err().to_string(), message);
+        }
+    }
+}
@@ -0,0 +1,223 @@
+use std::{
+    any::{Any, TypeId},
+    collections::{HashMap, HashSet},
+    rc::Rc,
+};
+
+use crate::{error::Error, ir::Function};
+
+/// A fact about one function, computed when first asked for and cached by an [`AnalysisManager`]
+/// until a transformation that doesn't preserve it runs.
+pub trait Analysis: Sized + 'static {
+    /// Whether the analysis only depends on the blocks and edges of the control flow graph, so that
+    /// [`Preserved::control_flow`] keeps it.
+    const CONTROL_FLOW: bool = false;
+
+    /// Computes the analysis, asking `analyses` for the ones it builds on.
+    fn compute(function: &Function, analyses: &mut AnalysisManager) -> Result<Self, Error>;
+}
+
+/// The cached analyses of one function.
+#[derive(Default)]
+pub struct AnalysisManager {
+    // The result of each analysis, with its `CONTROL_FLOW`.
+    cache: HashMap<TypeId, (Rc<dyn Any>, bool)>,
+}
+
+impl AnalysisManager {
+    /// The analysis `A` of `function`, which must be the function the cached results were computed on.
+    pub fn get<A: Analysis>(&mut self, function: &Function) -> Result<Rc<A>, Error> {
+        if let Some((result, _)) = self.cache.get(&TypeId::of::<A>()) {
+            return Ok(result.clone().downcast().unwrap());
+        }
+        let result = Rc::new(A::compute(function, self)?);
+        self.cache
+            .insert(TypeId::of::<A>(), (result.clone(), A::CONTROL_FLOW));
+        Ok(result)
+    }
+
+    /// Drops the analyses that `preserved` doesn't keep.
+    pub fn invalidate(&mut self, preserved: &Preserved) {
+        self.cache
+            .retain(|id, (_, control_flow)| preserved.keeps(id, *control_flow));
+    }
+
+    /// Drops every analysis, for when a transformation changes the function halfway.
+    pub fn clear(&mut self) {
+        self.cache.clear();
+    }
+}
+
+/// The analyses that are still valid after a transformation, like LLVM's `PreservedAnalyses`.
+#[derive(Default)]
+pub struct Preserved {
+    all: bool,
+    control_flow: bool,
+    analyses: HashSet<TypeId>,
+}
+
+impl Preserved {
+    /// For transformations that changed nothing.
+    pub fn all() -> Self {
+        Self {
+            all: true,
+            ..Default::default()
+        }
+    }
+
+    pub fn none() -> Self {
+        Self::default()
+    }
+
+    /// For transformations that change instructions but no labels or jumps, and so keep every
+    /// analysis of the control flow graph's shape.
+    pub fn control_flow() -> Self {
+        Self {
+            control_flow: true,
+            ..Default::default()
+        }
+    }
+
+    pub fn preserve<A: Analysis>(mut self) -> Self {
+        self.analyses.insert(TypeId::of::<A>());
+        self
+    }
+
+    fn keeps(&self, id: &TypeId, control_flow: bool) -> bool {
+        self.all || (self.control_flow && control_flow) || self.analyses.contains(id)
+    }
+}
+
+/// A transformation of one function.
+pub trait Transform {
+    /// Transforms `function`, returning what stayed valid of the analyses it started with.
+    fn run(
+        &self,
+        function: &mut Function,
+        analyses: &mut AnalysisManager,
+    ) -> Result<Preserved, Error>;
+}
+
+/// Runs transformations in order, sharing the analyses of a function between them.
+pub struct PassManager<T> {
+    passes: Vec<T>,
+}
+
+impl<T: Transform> PassManager<T> {
+    pub fn new(passes: Vec<T>) -> Self {
+        Self { passes }
+    }
+
+    pub fn run(&self, function: &mut Function) -> Result<(), Error> {
+        self.run_with(function, |_, _| Ok(()))
+    }
+
+    /// Runs the passes, showing `after` each pass with the function it left. An error from `after`
+    /// stops the run.
+    pub fn run_with(
+        &self,
+        function: &mut Function,
+        mut after: impl FnMut(&T, &Function) -> Result<(), Error>,
+    ) -> Result<(), Error> {
+        let mut analyses = AnalysisManager::default();
+        for pass in &self.passes {
+            let preserved = pass.run(function, &mut analyses)?;
+            analyses.invalidate(&preserved);
+            after(pass, function)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::cell::Cell;
+
+    use super::{Analysis, AnalysisManager, PassManager, Preserved, Transform};
+    use crate::{
+        error::Error,
+        ir::Function,
+        parser::parse,
+        ssa::{Cfg, DominanceFrontiers, Dominators},
+    };
+
+    thread_local! {
+        static COUNTED: Cell<usize> = const { Cell::new(0) };
+    }
+
+    // The number of instructions, counting how often it is computed.
+    struct Counted(usize);
+
+    impl Analysis for Counted {
+        fn compute(function: &Function, _: &mut AnalysisManager) -> Result<Self, Error> {
+            COUNTED.set(COUNTED.get() + 1);
+            Ok(Self(function.instrs.len()))
+        }
+    }
+
+    #[test]
+    fn test_analysis_manager() {
+        let txt = "@main(c: bool) {\n  br c .a .b;\n.a:\n  jmp .b;\n.b:\n  ret;\n}\n";
+        let function = &parse(txt).unwrap().functions[0];
+        let mut analyses = AnalysisManager::default();
+
+        assert_eq!(analyses.get::<Counted>(function).unwrap().0, 5);
+        assert_eq!(analyses.get::<Counted>(function).unwrap().0, 5);
+        assert_eq!(COUNTED.get(), 1);
+
+        analyses.invalidate(&Preserved::all());
+        analyses.get::<Counted>(function).unwrap();
+        assert_eq!(COUNTED.get(), 1);
+
+        analyses.invalidate(&Preserved::none().preserve::<Counted>());
+        analyses.get::<Counted>(function).unwrap();
+        assert_eq!(COUNTED.get(), 1);
+
+        let frontiers = analyses.get::<DominanceFrontiers>(function).unwrap();
+        let dominators = analyses.get::<Dominators>(function).unwrap();
+        assert_eq!(frontiers.0["a"], ["b".to_string()].into());
+
+        // The control flow analyses survive, and the rest is recomputed.
+        analyses.invalidate(&Preserved::control_flow());
+        assert!(std::rc::Rc::ptr_eq(
+            &dominators,
+            &analyses.get::<Dominators>(function).unwrap()
+        ));
+        analyses.get::<Cfg>(function).unwrap();
+        analyses.get::<Counted>(function).unwrap();
+        assert_eq!(COUNTED.get(), 2);
+
+        analyses.invalidate(&Preserved::none());
+        assert!(!std::rc::Rc::ptr_eq(
+            &dominators,
+            &analyses.get::<Dominators>(function).unwrap()
+        ));
+    }
+
+    // Removes the first instruction and checks the instruction count it was given.
+    struct Pop;
+
+    impl Transform for Pop {
+        fn run(
+            &self,
+            function: &mut Function,
+            analyses: &mut AnalysisManager,
+        ) -> Result<Preserved, Error> {
+            assert_eq!(analyses.get::<Counted>(function)?.0, function.instrs.len());
+            function.instrs.remove(0);
+            Ok(Preserved::none())
+        }
+    }
+
+    #[test]
+    fn test_pass_manager() {
+        let txt = "@main {\n  a: int = const 1;\n  b: int = const 2;\n  print a;\n}\n";
+        let mut bril = parse(txt).unwrap();
+
+        PassManager::new(vec![Pop, Pop])
+            .run(&mut bril.functions[0])
+            .unwrap();
+        assert_eq!(bril.functions[0].instrs.len(), 1);
+        assert_eq!(COUNTED.get(), 2);
+    }
+}
@@ -0,0 +1,216 @@
+use std::fmt;
+
+use crate::ir::{Argument, Bril, Function, Instruction, Literal};
+
+// Renders programs in the Bril text format, byte for byte the way `bril2txt` does.
+
+impl fmt::Display for Bril {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for function in &self.functions {
+            write!(f, "{function}")?;
+        }
+        Ok(())
+    }
+}
+
+impl Function {
+    /// The line that opens the function, `@name(args): type {`.
+    pub fn header(&self) -> String {
+        let mut header = format!("@{}", self.name);
+        if let Some(args) = self.args.as_ref().filter(|args| !args.is_empty()) {
+            let args = args
+                .iter()
+                .map(Argument::to_string)
+                .collect::<Vec<_>>()
+                .join(", ");
+            header.push_str(&format!("({args})"));
+        }
+        if let Some(r#type) = &self.r#type {
+            header.push_str(&format!(": {type}"));
+        }
+        header.push_str(" {");
+        header
+    }
+}
+
+impl fmt::Display for Function {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "{}", self.header())?;
+
+        for instr in &self.instrs {
+            match instr {
+                Instruction::Label { label, .. } => writeln!(f, ".{label}:")?,
+                _ => writeln!(f, "  {instr};")?,
+            }
+        }
+
+        writeln!(f, "}}")
+    }
+}
+
+impl fmt::Display for Argument {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}", self.name, self.r#type)
+    }
+}
+
+/// Prints a label as `.label:` and any other instruction without its trailing `;`.
+impl fmt::Display for Instruction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Instruction::Label { label, .. } => write!(f, ".{label}:"),
+            Instruction::Constant {
+                dest,
+                r#type,
+                value,
+                ..
+            } => write!(f, "{dest}: {type} = const {value}"),
+            Instruction::Value {
+                op,
+                dest,
+                r#type,
+                args,
+                funcs,
+                labels,
+                ..
+            } => {
+                write!(f, "{dest}: {type} = {op}")?;
+                write_operands(f, args, funcs, labels)
+            }
+            Instruction::Effect {
+                op,
+                args,
+                funcs,
+                labels,
+                ..
+            } => {
+                write!(f, "{op}")?;
+                write_operands(f, args, funcs, labels)
+            }
+        }
+    }
+}
+
+fn write_operands(
+    f: &mut fmt::Formatter<'_>,
+    args: &[String],
+    funcs: &[String],
+    labels: &[String],
+) -> fmt::Result {
+    for func in funcs {
+        write!(f, " @{func}")?;
+    }
+    for arg in args {
+        write!(f, " {arg}")?;
+    }
+    for label in labels {
+        write!(f, " .{label}")?;
+    }
+    Ok(())
+}
+
+impl fmt::Display for Literal {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Literal::Int(i) => write!(f, "{i}"),
+            Literal::Bool(b) => write!(f, "{b}"),
+            Literal::Float(x) => write!(f, "{}", python_float(*x)),
+            Literal::Char(c) => match c {
+                '\0' => write!(f, "'\\0'"),
+                '\u{07}' => write!(f, "'\\a'"),
+                '\u{08}' => write!(f, "'\\b'"),
+                '\t' => write!(f, "'\\t'"),
+                '\n' => write!(f, "'\\n'"),
+                '\u{0b}' => write!(f, "'\\v'"),
+                '\u{0c}' => write!(f, "'\\f'"),
+                '\r' => write!(f, "'\\r'"),
+                c => write!(f, "'{c}'"),
+            },
+        }
+    }
+}
+
+// `bril2txt` prints floats with Python's `repr`: the shortest round-tripping digits,
+// in positional notation for exponents in -4..16 and scientific notation otherwise.
+pub(crate) fn python_float(x: f64) -> String {
+    if x.is_nan() {
+        return "nan".to_string();
+    }
+    if x.is_infinite() {
+        return if x > 0.0 { "inf" } else { "-inf" }.to_string();
+    }
+
+    let sign = if x.is_sign_negative() { "-" } else { "" };
+    let scientific = format!("{:e}", x.abs());
+    let (mantissa, exponent) = scientific.split_once('e').unwrap();
+    let exponent: i32 = exponent.parse().unwrap();
+    let digits = mantissa.replace('.', "");
+
+    if (-4..16).contains(&exponent) {
+        if exponent >= 0 {
+            let point = exponent as usize + 1;
+            let padded = format!("{digits:0<point$}");
+            let (int, frac) = padded.split_at(point);
+            let frac = if frac.is_empty() { "0" } else { frac };
+            format!("{sign}{int}.{frac}")
+        } else {
+            let zeros = "0".repeat((-exponent - 1) as usize);
+            format!("{sign}0.{zeros}{digits}")
+        }
+    } else {
+        let exponent_sign = if exponent < 0 { '-' } else { '+' };
+        format!("{sign}{mantissa}e{exponent_sign}{:02}", exponent.abs())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use insta::glob;
+
+    use super::python_float;
+    use crate::ir::Bril;
+
+    #[test]
+    fn test_print() {
+        glob!("..", "tests/test/print/*.json", |path| {
+            let json = std::fs::read_to_string(path).unwrap();
+            let expected = std::fs::read_to_string(path.with_extension("bril")).unwrap();
+            let bril: Bril = serde_json::from_str(&json).unwrap();
+
+            assert_eq!(bril.to_string(), expected);
+        });
+    }
+
+    #[test]
+    fn test_roundtrip() {
+        glob!("..", "tests/{examples,test/interp}/**/*.bril", |path| {
+            let txt = std::fs::read_to_string(path).unwrap();
+            let printed = crate::parser::parse(&txt).unwrap().to_string();
+            let reprinted = crate::parser::parse(&printed).unwrap().to_string();
+
+            assert_eq!(printed, reprinted);
+        });
+    }
+
+    #[test]
+    fn test_python_float() {
+        let cases = [
+            (1.0, "1.0"),
+            (0.3, "0.3"),
+            (-0.0, "-0.0"),
+            (1000000.0, "1000000.0"),
+            (-1000000.0, "-1000000.0"),
+            (110000000000.0, "110000000000.0"),
+            (1e16, "1e+16"),
+            (1.5e16, "1.5e+16"),
+            (0.0001, "0.0001"),
+            (0.00001, "1e-05"),
+            (1.25e-7, "1.25e-07"),
+            (f64::INFINITY, "inf"),
+        ];
+
+        for (x, expected) in cases {
+            assert_eq!(python_float(x), expected);
+        }
+    }
+}
@@ -1,50 +1,96 @@
 use std::collections::{HashMap, HashSet};
 
-use crate::{basic_block::BasicBlock, Argument, Function};
-
-pub struct CfgEntry {
+use crate::{
+    basic_block::{partition, BasicBlock, Labeler},
+    error::Error,
+    ir::{Argument, Extra, Function, Instruction, Op, Type},
+    pass_manager::{Analysis, AnalysisManager},
+};
+
+#[derive(Clone)]
+struct CfgEntry {
     basic_block: BasicBlock,
     predesessors: HashSet<String>,
     successors: HashSet<String>,
 }
 
+/// The control flow graph of a function: its basic blocks by label, with the edges between them.
+#[derive(Clone)]
 pub struct Cfg {
     arguments: Vec<Argument>,
     entry: String,
+    // Block labels in program order.
+    order: Vec<String>,
     graph: HashMap<String, CfgEntry>,
 }
 
 impl Cfg {
-    pub fn new(function: &Function) -> Self {
+    /// The graph of `function`'s blocks as [`BasicBlock::new_blocks`] makes them. Fails for a
+    /// function without instructions, a label defined twice, a jump to an undefined label or a
+    /// `br` without one argument and two labels.
+    pub fn new(function: &Function) -> Result<Self, Error> {
         let basic_blocks = BasicBlock::new_blocks(&function.instrs);
+        let invalid = |message: String| Error::InvalidCfg {
+            function: function.name.clone(),
+            message,
+        };
 
         let arguments = function.args.clone().unwrap_or_default();
-        let entry = basic_blocks[0][0].label.clone().unwrap();
+        let Some(entry) = basic_blocks.first() else {
+            return Err(invalid("function has no instructions".to_string()));
+        };
+        let entry = entry[0].label().unwrap().to_string();
+
+        let order: Vec<String> = basic_blocks
+            .iter()
+            .map(|block| block[0].label().unwrap().to_string())
+            .collect();
+        let mut labels = HashSet::new();
+        for label in &order {
+            if !labels.insert(label) {
+                return Err(invalid(format!("label .{label} is defined more than once")));
+            }
+        }
+        for instr in basic_blocks.iter().flat_map(|block| block.iter()) {
+            if instr.op() == Some(Op::Br) && (instr.args().len() != 1 || instr.labels().len() != 2)
+            {
+                return Err(invalid(format!(
+                    "`{instr}` should have one argument and two labels"
+                )));
+            }
+            if matches!(instr.op(), Some(Op::Br | Op::Jmp | Op::Guard)) {
+                if let Some(label) = instr.labels().iter().find(|label| !labels.contains(label)) {
+                    return Err(Error::MissingLabel {
+                        function: function.name.clone(),
+                        label: label.clone(),
+                        pos: instr.pos(),
+                    });
+                }
+            }
+        }
 
         let mut predesessors = HashMap::new();
         let mut successors = HashMap::new();
 
         for block in &basic_blocks {
-            let label = block[0].label.clone().unwrap();
-            if let Some(nexts) = block.last().unwrap().labels.as_ref() {
-                for next in nexts {
-                    let next = next.clone();
-                    successors
-                        .entry(label.clone())
-                        .or_insert_with(HashSet::new)
-                        .insert(next.clone());
-                    predesessors
-                        .entry(next)
-                        .or_insert_with(HashSet::new)
-                        .insert(label.clone());
-                }
+            let label = block[0].label().unwrap().to_string();
+            for next in block.successors() {
+                let next = next.clone();
+                successors
+                    .entry(label.clone())
+                    .or_insert_with(HashSet::new)
+                    .insert(next.clone());
+                predesessors
+                    .entry(next)
+                    .or_insert_with(HashSet::new)
+                    .insert(label.clone());
             }
         }
 
         let graph = basic_blocks
             .into_iter()
             .map(|block| {
-                let label = block[0].label.clone().unwrap();
+                let label = block[0].label().unwrap().to_string();
                 let predesessors = predesessors.remove(&label).unwrap_or_default();
                 let successors = successors.remove(&label).unwrap_or_default();
                 (
@@ -58,11 +104,31 @@ impl Cfg {
             })
             .collect();
 
-        Self {
+        Ok(Self {
             arguments,
             entry,
+            order,
             graph,
-        }
+        })
+    }
+
+    pub fn entry(&self) -> &str {
+        &self.entry
+    }
+
+    /// The basic blocks in program order.
+    pub fn blocks(&self) -> impl Iterator<Item = &BasicBlock> {
+        self.order
+            .iter()
+            .map(|label| &self.graph[label].basic_block)
+    }
+
+    pub fn predecessors(&self, label: &str) -> &HashSet<String> {
+        &self.graph[label].predesessors
+    }
+
+    pub fn successors(&self, label: &str) -> &HashSet<String> {
+        &self.graph[label].successors
     }
 
     pub fn reverse_post_order(&self) -> Vec<&str> {
@@ -90,7 +156,8 @@ impl Cfg {
         order
     }
 
-    pub fn dominators(&self) -> HashMap<&str, HashSet<&str>> {
+    /// The blocks dominating each reachable block, itself included.
+    pub fn dominators(&self) -> HashMap<String, HashSet<String>> {
         let mut dominators = HashMap::new();
         let order = self.reverse_post_order();
 
@@ -102,12 +169,17 @@ impl Cfg {
         while changed {
             changed = false;
             for &label in &order {
-                let predecessors = &self.graph[label].predesessors;
-                let mut new_doominators = if predecessors.is_empty() {
+                // Unreachable predecessors don't constrain anything, and nothing comes before the entry.
+                let predecessors = self.graph[label]
+                    .predesessors
+                    .iter()
+                    .filter(|pred| dominators.contains_key(pred.as_str()))
+                    .collect::<Vec<_>>();
+                let mut new_doominators = if predecessors.is_empty() || label == self.entry {
                     Default::default()
                 } else {
                     let mut new_dominants = order.iter().copied().collect::<HashSet<_>>();
-                    for pred in &self.graph[label].predesessors {
+                    for pred in predecessors {
                         new_dominants = new_dominants
                             .intersection(&dominators[pred.as_str()])
                             .copied()
@@ -126,18 +198,32 @@ impl Cfg {
         }
 
         dominators
+            .into_iter()
+            .map(|(label, dom)| {
+                (
+                    label.to_string(),
+                    dom.into_iter().map(str::to_string).collect(),
+                )
+            })
+            .collect()
     }
 
-    pub fn dominant_fronteers(&self) -> HashMap<&str, HashSet<&str>> {
-        let mut dominant_fronteers: HashMap<&str, HashSet<&str>> = HashMap::new();
-        let dominators = self.dominators();
+    pub fn dominant_fronteers(
+        &self,
+        dominators: &HashMap<String, HashSet<String>>,
+    ) -> HashMap<String, HashSet<String>> {
+        let mut dominant_fronteers: HashMap<String, HashSet<String>> = HashMap::new();
 
-        for (&b, dom) in &dominators {
-            for &a in dom {
+        for (b, dom) in dominators {
+            for a in dom {
                 // a dominates b
                 for c in &self.graph[b].successors {
-                    if !dominators[c.as_str()].contains(b) && a != c {
-                        dominant_fronteers.entry(a).or_default().insert(c.as_str());
+                    // a doesn't strictly dominate c
+                    if a == c || !dominators[c].contains(a) {
+                        dominant_fronteers
+                            .entry(a.clone())
+                            .or_default()
+                            .insert(c.clone());
                     }
                 }
             }
@@ -146,20 +232,25 @@ impl Cfg {
         dominant_fronteers
     }
 
-    pub fn insert_phi(&mut self) {
+    pub fn insert_phi(&mut self, dominant_fronteers: &HashMap<String, HashSet<String>>) {
         let mut phis: HashMap<String, HashMap<String, HashMap<String, String>>> = HashMap::new();
 
         let mut defs: HashMap<&str, HashSet<&str>> = HashMap::new();
+        let mut types: HashMap<String, Type> = self
+            .arguments
+            .iter()
+            .map(|arg| (arg.name.clone(), arg.r#type.clone()))
+            .collect();
 
         for (label, entry) in &self.graph {
             for instr in entry.basic_block.as_ref() {
-                if let Some(dest) = &instr.dest {
+                if let (Some(dest), Some(r#type)) = (instr.dest(), instr.r#type()) {
                     defs.entry(dest).or_default().insert(label.as_str());
+                    types.insert(dest.to_string(), r#type.clone());
                 }
             }
         }
 
-        let dominant_fronteers = self.dominant_fronteers();
         for (var, defs) in defs {
             let mut visited = HashSet::new();
             let mut stack = defs.iter().copied().collect::<Vec<_>>();
@@ -168,12 +259,16 @@ impl Cfg {
                 if !visited.insert(d) {
                     continue;
                 }
-                for &block in dominant_fronteers.get(d).unwrap_or(&Default::default()) {
-                    phis.entry(block.to_string())
+                for block in dominant_fronteers.get(d).into_iter().flatten() {
+                    // One argument per incoming edge; `rename` replaces them with the reaching definitions.
+                    let aliases = phis
+                        .entry(block.to_string())
                         .or_default()
                         .entry(var.to_string())
-                        .or_default()
-                        .insert(d.to_string(), var.to_string());
+                        .or_default();
+                    for pred in &self.graph[block.as_str()].predesessors {
+                        aliases.insert(pred.clone(), var.to_string());
+                    }
 
                     stack.push(block);
                 }
@@ -182,7 +277,7 @@ impl Cfg {
 
         for (label, block) in self.graph.iter_mut() {
             if let Some(phis) = phis.remove(label.as_str()) {
-                block.basic_block.insert_phi(&phis);
+                block.basic_block.insert_phi(&phis, &types);
             }
         }
     }
@@ -190,7 +285,7 @@ impl Cfg {
     fn _rename(
         &mut self,
         block: &str,
-        dominators: &HashMap<String, HashSet<String>>,
+        children: &HashMap<String, Vec<String>>,
         stack: &mut HashMap<String, Vec<String>>,
         counter: &mut HashMap<String, usize>,
     ) {
@@ -198,18 +293,16 @@ impl Cfg {
         let cfg_entry = self.graph.get_mut(block).unwrap();
         for instr in &mut cfg_entry.basic_block.0 {
             // replace each argument to instr with stack[old name]
-            if let Some(args) = &mut instr.args {
-                if instr.op.as_deref() != Some("phi") {
-                    for arg in args {
-                        if let Some(stack) = stack.get(arg) {
-                            *arg = stack.last().unwrap().clone();
-                        }
+            if instr.op() != Some(Op::Phi) {
+                for arg in instr.args_mut() {
+                    if let Some(stack) = stack.get(arg.as_str()) {
+                        *arg = stack.last().unwrap().clone();
                     }
                 }
             }
 
             // replace instr's destination with a new name
-            if let Some(dest) = instr.dest.as_mut() {
+            if let Some(dest) = instr.dest_mut() {
                 let n = counter.entry(dest.clone()).or_default();
                 *n += 1;
                 let new_name = format!("{}.{}", dest, n);
@@ -225,10 +318,15 @@ impl Cfg {
         let succs = cfg_entry.successors.clone();
         for s in &succs {
             for instr in &mut self.graph.get_mut(s.as_str()).unwrap().basic_block.0 {
-                if instr.op.as_deref() == Some("phi") {
-                    for i in 0..instr.args.as_ref().unwrap().len() {
-                        if instr.labels.as_ref().unwrap()[i] == block {
-                            let arg = &mut instr.args.as_mut().unwrap()[i];
+                if let Instruction::Value {
+                    op: Op::Phi,
+                    args,
+                    labels,
+                    ..
+                } = instr
+                {
+                    for (arg, label) in args.iter_mut().zip(labels.iter()) {
+                        if label == block {
                             if let Some(stack) = stack.get(arg.as_str()) {
                                 *arg = stack.last().unwrap().clone();
                             }
@@ -238,51 +336,305 @@ impl Cfg {
             }
         }
 
-        for s in &succs {
-            if dominators[s.as_str()].contains(block) {
-                self._rename(s, dominators, stack, counter);
-            }
+        for child in children.get(block).into_iter().flatten() {
+            self._rename(child, children, stack, counter);
         }
 
         *stack = old_stack;
     }
 
-    pub fn rename(&mut self) {
+    /// Children of each block in the dominator tree, sorted so that renaming is deterministic.
+    pub fn dominator_tree(
+        dominators: &HashMap<String, HashSet<String>>,
+    ) -> HashMap<String, Vec<String>> {
+        let mut children: HashMap<String, Vec<String>> = HashMap::new();
+
+        for (label, dom) in dominators {
+            // The immediate dominator is the strict dominator that is dominated by all the others.
+            let idom = dom
+                .iter()
+                .filter(|&d| d != label)
+                .max_by_key(|&d| dominators[d].len());
+            if let Some(idom) = idom {
+                children
+                    .entry(idom.clone())
+                    .or_default()
+                    .push(label.clone());
+            }
+        }
+        for children in children.values_mut() {
+            children.sort();
+        }
+
+        children
+    }
+
+    pub fn rename(&mut self, dominator_tree: &HashMap<String, Vec<String>>) {
         let entry = self.entry.clone();
         self._rename(
             &entry,
-            &self
-                .dominators()
-                .into_iter()
-                .map(|(k, v)| {
-                    (
-                        k.to_string(),
-                        v.into_iter().map(|s| s.to_string()).collect(),
-                    )
-                })
-                .collect(),
+            dominator_tree,
             &mut Default::default(),
             &mut Default::default(),
         );
     }
+
+    /// The instructions of all blocks, in their original order.
+    pub fn into_instrs(mut self) -> Vec<Instruction> {
+        self.order
+            .iter()
+            .flat_map(|label| self.graph.remove(label).unwrap().basic_block.0)
+            .collect()
+    }
+}
+
+impl Analysis for Cfg {
+    fn compute(function: &Function, _: &mut AnalysisManager) -> Result<Self, Error> {
+        Cfg::new(function)
+    }
+}
+
+/// [`Cfg::dominators`] as an analysis.
+pub struct Dominators(pub HashMap<String, HashSet<String>>);
+
+impl Analysis for Dominators {
+    const CONTROL_FLOW: bool = true;
+
+    fn compute(function: &Function, analyses: &mut AnalysisManager) -> Result<Self, Error> {
+        Ok(Self(analyses.get::<Cfg>(function)?.dominators()))
+    }
+}
+
+/// [`Cfg::dominator_tree`] as an analysis.
+pub struct DominatorTree(pub HashMap<String, Vec<String>>);
+
+impl Analysis for DominatorTree {
+    const CONTROL_FLOW: bool = true;
+
+    fn compute(function: &Function, analyses: &mut AnalysisManager) -> Result<Self, Error> {
+        Ok(Self(Cfg::dominator_tree(
+            &analyses.get::<Dominators>(function)?.0,
+        )))
+    }
+}
+
+/// [`Cfg::dominant_fronteers`] as an analysis.
+pub struct DominanceFrontiers(pub HashMap<String, HashSet<String>>);
+
+impl Analysis for DominanceFrontiers {
+    const CONTROL_FLOW: bool = true;
+
+    fn compute(function: &Function, analyses: &mut AnalysisManager) -> Result<Self, Error> {
+        let dominators = analyses.get::<Dominators>(function)?;
+        Ok(Self(
+            analyses
+                .get::<Cfg>(function)?
+                .dominant_fronteers(&dominators.0),
+        ))
+    }
+}
+
+/// Gives `function` an entry block of its own when its first block is a jump target, as bril's
+/// `cfg.py` does, so that nothing comes before the entry. Returns whether it added one.
+pub fn add_entry(function: &mut Function) -> bool {
+    let Some(entry) = function.instrs.first().and_then(Instruction::label) else {
+        return false;
+    };
+    let targeted = function
+        .instrs
+        .iter()
+        .any(|instr| instr.labels().iter().any(|label| label == entry));
+    if targeted {
+        let label = Labeler::new(&partition(&function.instrs)).label("entry");
+        function.instrs.insert(
+            0,
+            Instruction::Label {
+                label,
+                pos: None,
+                extra: Extra::new(),
+            },
+        );
+    }
+    targeted
+}
+
+/// Fails for a function that speculates, for transformations that don't know a failing `guard`
+/// rolls variables back to what they were at `speculate`.
+pub fn no_speculation(function: &Function, transformation: &str) -> Result<(), Error> {
+    let speculates = function
+        .instrs
+        .iter()
+        .any(|instr| matches!(instr.op(), Some(Op::Speculate | Op::Commit | Op::Guard)));
+    if speculates {
+        return Err(Error::Unsupported {
+            function: function.name.clone(),
+            message: format!("{transformation} doesn't support speculation"),
+        });
+    }
+    Ok(())
+}
+
+/// Converts `function` to SSA form by inserting phi nodes at the dominance frontiers of the
+/// definitions and renaming every definition to `name.n`. Arguments keep their names.
+pub fn to_ssa(function: &mut Function, analyses: &mut AnalysisManager) -> Result<(), Error> {
+    if function.instrs.is_empty() {
+        return Ok(());
+    }
+    no_speculation(function, "SSA conversion")?;
+
+    // Phi nodes in the entry block need a labeled predecessor for the edge from the function's start.
+    if add_entry(function) {
+        analyses.clear();
+    }
+
+    let mut cfg = Cfg::clone(&*analyses.get::<Cfg>(function)?);
+    cfg.insert_phi(&analyses.get::<DominanceFrontiers>(function)?.0);
+    cfg.rename(&analyses.get::<DominatorTree>(function)?.0);
+    function.instrs = cfg.into_instrs();
+    Ok(())
+}
+
+/// Converts `function` out of SSA form by replacing each phi node with copies at the end of its
+/// predecessors. Edges from blocks with several successors get a block of their own for the copies.
+/// The copies run in the order of the phi nodes, which the interpreter also evaluates one by one.
+/// Arguments that are never defined are skipped, as the phi leaves its destination undefined.
+pub fn from_ssa(function: &mut Function, analyses: &mut AnalysisManager) -> Result<(), Error> {
+    if function.instrs.is_empty() {
+        return Ok(());
+    }
+    no_speculation(function, "conversion out of SSA")?;
+
+    let mut blocks = analyses
+        .get::<Cfg>(function)?
+        .blocks()
+        .cloned()
+        .collect::<Vec<_>>();
+    let mut defined: HashSet<String> = function
+        .args
+        .iter()
+        .flatten()
+        .map(|arg| arg.name.clone())
+        .collect();
+    defined.extend(
+        blocks
+            .iter()
+            .flat_map(|block| block.iter())
+            .filter_map(|instr| instr.dest().map(str::to_string)),
+    );
+
+    // Copies per edge, from the predecessor's label to the phi's.
+    let mut copies: HashMap<(String, String), Vec<Instruction>> = HashMap::new();
+    for block in &mut blocks {
+        let label = block[0].label().unwrap().to_string();
+        block.0.retain(|instr| match instr {
+            Instruction::Value {
+                op: Op::Phi,
+                dest,
+                r#type,
+                args,
+                labels,
+                ..
+            } => {
+                for (arg, pred) in args.iter().zip(labels) {
+                    if defined.contains(arg) {
+                        copies
+                            .entry((pred.clone(), label.clone()))
+                            .or_default()
+                            .push(Instruction::Value {
+                                op: Op::Id,
+                                dest: dest.clone(),
+                                r#type: r#type.clone(),
+                                args: vec![arg.clone()],
+                                funcs: vec![],
+                                labels: vec![],
+                                pos: None,
+                                extra: Extra::new(),
+                            });
+                    }
+                }
+                false
+            }
+            _ => true,
+        });
+    }
+
+    let mut labeler = Labeler::new(
+        &blocks
+            .iter()
+            .map(|block| block.0.clone())
+            .collect::<Vec<_>>(),
+    );
+
+    let mut split = Vec::new();
+    for block in &mut blocks {
+        let label = block[0].label().unwrap().to_string();
+        let succs = block.successors().cloned().collect::<HashSet<_>>();
+        // Sorted so that the output doesn't depend on hash map order.
+        let mut succs = succs.into_iter().collect::<Vec<_>>();
+        succs.sort();
+
+        for succ in succs {
+            let Some(edge) = copies.remove(&(label.clone(), succ.clone())) else {
+                continue;
+            };
+            if block.successors().count() == 1 {
+                let end = block.len() - 1;
+                block.0.splice(end..end, edge);
+            } else {
+                let middle = labeler.label(&format!("{label}.{succ}."));
+                for instr in &mut block.0 {
+                    if matches!(instr.op(), Some(Op::Br | Op::Jmp | Op::Guard)) {
+                        for target in instr.labels_mut() {
+                            if *target == succ {
+                                *target = middle.clone();
+                            }
+                        }
+                    }
+                }
+                let mut instrs = vec![Instruction::Label {
+                    label: middle,
+                    pos: None,
+                    extra: Extra::new(),
+                }];
+                instrs.extend(edge);
+                instrs.push(Instruction::Effect {
+                    op: Op::Jmp,
+                    args: vec![],
+                    funcs: vec![],
+                    labels: vec![succ],
+                    pos: None,
+                    extra: Extra::new(),
+                });
+                split.push(BasicBlock(instrs));
+            }
+        }
+    }
+
+    function.instrs = blocks
+        .into_iter()
+        .chain(split)
+        .flat_map(|block| block.0)
+        .collect();
+    Ok(())
 }
 
 #[cfg(test)]
 mod test {
     use std::collections::{BTreeMap, BTreeSet};
 
-    use insta::{assert_display_snapshot, glob};
+    use insta::{assert_snapshot, glob};
 
     use crate::{
         basic_block::BasicBlock,
+        ir::{Bril, Op},
+        pass_manager::AnalysisManager,
         test::{bril2json, bril2txt, brili},
-        Bril,
     };
 
     #[test]
     fn test_dominators_dot() {
         glob!("..", "tests/examples/df/*.bril", |path| {
-            let txt = std::fs::read_to_string(&path).unwrap();
+            let txt = std::fs::read_to_string(path).unwrap();
             let json = bril2json(&txt);
             let bril: Bril = serde_json::from_str(&json).unwrap();
 
@@ -290,7 +642,7 @@ mod test {
             output.push_str("\n\n");
 
             for function in bril.functions {
-                let cfg = crate::ssa::Cfg::new(&function);
+                let cfg = crate::ssa::Cfg::new(&function).unwrap();
                 let dominators = cfg.dominators();
 
                 output.push_str(&format!("function {}:\n", function.name));
@@ -299,19 +651,19 @@ mod test {
                     for dominator in dominators.into_iter().collect::<BTreeSet<_>>() {
                         output.push_str(&format!("{} ", dominator));
                     }
-                    output.push_str("\n");
+                    output.push('\n');
                 }
-                output.push_str("\n");
+                output.push('\n');
             }
 
-            assert_display_snapshot!(output);
+            assert_snapshot!(output);
         });
     }
 
     #[test]
     fn test_insert_phi() {
         glob!("..", "tests/examples/ssa/*.bril", |path| {
-            let txt = std::fs::read_to_string(&path).unwrap();
+            let txt = std::fs::read_to_string(path).unwrap();
             let json = bril2json(&txt);
             let mut bril: Bril = serde_json::from_str(&json).unwrap();
 
@@ -319,8 +671,9 @@ mod test {
             output.push_str("\n\n");
 
             for function in &mut bril.functions {
-                let mut cfg = crate::ssa::Cfg::new(&function);
-                cfg.insert_phi();
+                let mut cfg = crate::ssa::Cfg::new(function).unwrap();
+                let dominant_fronteers = cfg.dominant_fronteers(&cfg.dominators());
+                cfg.insert_phi(&dominant_fronteers);
 
                 // !!
                 let basic_blocks = BasicBlock::new_blocks(function.instrs.as_slice());
@@ -328,8 +681,8 @@ mod test {
                 function.instrs = basic_blocks
                     .into_iter()
                     .flat_map(|block| {
-                        let label = block[0].label.clone().unwrap();
-                        cfg.graph[&label].basic_block.iter().cloned()
+                        let label = block[0].label().unwrap();
+                        cfg.graph[label].basic_block.iter().cloned()
                     })
                     .collect::<Vec<_>>();
             }
@@ -341,7 +694,7 @@ mod test {
 
             assert_eq!(orig.0, after.0);
 
-            assert_display_snapshot!(format!(
+            assert_snapshot!(format!(
                 "{}\n\n{} -> {}\n\n{}",
                 txt,
                 orig.1,
@@ -354,7 +707,7 @@ mod test {
     #[test]
     fn test_rename() {
         glob!("..", "tests/examples/ssa/loop-orig.bril", |path| {
-            let txt = std::fs::read_to_string(&path).unwrap();
+            let txt = std::fs::read_to_string(path).unwrap();
             let json = bril2json(&txt);
             let mut bril: Bril = serde_json::from_str(&json).unwrap();
 
@@ -362,9 +715,10 @@ mod test {
             output.push_str("\n\n");
 
             for function in &mut bril.functions {
-                let mut cfg = crate::ssa::Cfg::new(&function);
-                cfg.insert_phi();
-                cfg.rename();
+                let mut cfg = crate::ssa::Cfg::new(function).unwrap();
+                let dominant_fronteers = cfg.dominant_fronteers(&cfg.dominators());
+                cfg.insert_phi(&dominant_fronteers);
+                cfg.rename(&crate::ssa::Cfg::dominator_tree(&cfg.dominators()));
 
                 // !!
                 let basic_blocks = BasicBlock::new_blocks(function.instrs.as_slice());
@@ -372,8 +726,8 @@ mod test {
                 function.instrs = basic_blocks
                     .into_iter()
                     .flat_map(|block| {
-                        let label = block[0].label.clone().unwrap();
-                        cfg.graph[&label].basic_block.iter().cloned()
+                        let label = block[0].label().unwrap();
+                        cfg.graph[label].basic_block.iter().cloned()
                     })
                     .collect::<Vec<_>>();
             }
@@ -387,7 +741,7 @@ mod test {
 
             assert_eq!(orig.0, after.0);
 
-            assert_display_snapshot!(format!(
+            assert_snapshot!(format!(
                 "{}\n\n{} -> {}\n\n{}",
                 txt,
                 orig.1,
@@ -396,4 +750,51 @@ mod test {
             ));
         });
     }
+
+    // Output of running `bril`, or the error it failed with.
+    fn run(bril: &Bril, args: &[String]) -> String {
+        let mut out = Vec::new();
+        let limits = crate::interp::Limits {
+            instructions: Some(100_000),
+            ..Default::default()
+        };
+        let result = crate::interp::run_limited(bril, args, &mut out, limits);
+        let mut out = String::from_utf8(out).unwrap();
+        if let Err(e) = result {
+            out.push_str(&format!("error: {}\n", e.message));
+        }
+        out
+    }
+
+    #[test]
+    fn test_ssa_roundtrip() {
+        glob!(
+            "..",
+            "tests/{examples/ssa_roundtrip,test/interp/core,test/interp/mem,test/interp/float}/*.bril",
+            |path| {
+                let txt = std::fs::read_to_string(path).unwrap();
+                let args = crate::bench::source_args(&txt);
+                let mut bril = crate::parser::parse(&txt).unwrap();
+                let expected = run(&bril, &args);
+
+                for function in &mut bril.functions {
+                    crate::ssa::to_ssa(function, &mut AnalysisManager::default()).unwrap();
+                }
+                let errors = crate::verify::verify_ssa(&bril);
+                assert!(errors.is_empty(), "{errors:?}\n{bril}");
+                assert_eq!(run(&bril, &args), expected, "{bril}");
+
+                for function in &mut bril.functions {
+                    crate::ssa::from_ssa(function, &mut AnalysisManager::default()).unwrap();
+                }
+                let errors = crate::verify::verify(&bril);
+                assert!(errors.is_empty(), "{errors:?}\n{bril}");
+                assert!(bril
+                    .functions
+                    .iter()
+                    .all(|function| function.instrs.iter().all(|instr| instr.op() != Some(Op::Phi))));
+                assert_eq!(run(&bril, &args), expected, "{bril}");
+            }
+        );
+    }
 }
@@ -0,0 +1,350 @@
+use std::fmt;
+
+use crate::ir::{Function, Instruction, Op};
+
+/// What one pass did to one function.
+#[derive(Debug, PartialEq, Eq)]
+pub struct PassStats {
+    pub pass: String,
+    /// Instructions before and after the pass, labels not included.
+    pub before: usize,
+    pub after: usize,
+    /// Instructions that became an `id` of another variable.
+    pub id: usize,
+    /// Instructions that got a new destination, like `x_prime`.
+    pub renamed: usize,
+    pub removed: usize,
+    pub added: usize,
+    /// The function's text as a unified diff, when asked for.
+    pub diff: Option<String>,
+}
+
+impl PassStats {
+    pub fn new(pass: impl Into<String>, before: &Function, after: &Function, diff: bool) -> Self {
+        let old = instructions(before);
+        let new = instructions(after);
+        let old_lines = old.iter().map(ToString::to_string).collect::<Vec<_>>();
+        let new_lines = new.iter().map(ToString::to_string).collect::<Vec<_>>();
+
+        let pass = pass.into();
+        let mut stats = Self {
+            before: old.len(),
+            after: new.len(),
+            id: 0,
+            renamed: 0,
+            removed: 0,
+            added: 0,
+            diff: diff.then(|| {
+                let name = &before.name;
+                unified(
+                    &format!("@{name} before {pass}"),
+                    &format!("@{name} after {pass}"),
+                    &before.to_string().lines().collect::<Vec<_>>(),
+                    &after.to_string().lines().collect::<Vec<_>>(),
+                )
+            }),
+            pass,
+        };
+
+        // Within each run of changed lines, pair what was added with what it replaced.
+        let edits = edits(&old_lines, &new_lines);
+        let mut i = 0;
+        while i < edits.len() {
+            let mut removed = Vec::new();
+            let mut added = Vec::new();
+            while let Some(edit) = edits.get(i).filter(|edit| !matches!(edit, Edit::Keep(..))) {
+                match *edit {
+                    Edit::Remove(a) => removed.push(Some(old[a])),
+                    Edit::Add(b) => added.push(new[b]),
+                    Edit::Keep(..) => unreachable!(),
+                }
+                i += 1;
+            }
+            if removed.is_empty() && added.is_empty() {
+                i += 1;
+                continue;
+            }
+            stats.pair(&mut removed, added);
+        }
+        stats
+    }
+
+    // Pairs each added instruction with a removed one: first the same destination and operation,
+    // which also pairs effects with new arguments, then the same destination, then, for renames,
+    // the same operation, and last any other destination in order.
+    fn pair(&mut self, removed: &mut [Option<&Instruction>], added: Vec<&Instruction>) {
+        type Matches = fn(&Instruction, &Instruction) -> bool;
+        let rules: [Matches; 4] = [
+            |old, new| old.dest() == new.dest() && old.op() == new.op(),
+            |old, new| old.dest().is_some() && old.dest() == new.dest(),
+            |old, new| old.dest().is_some() && new.dest().is_some() && old.op() == new.op(),
+            |old, new| old.dest().is_some() && new.dest().is_some(),
+        ];
+        let mut added = added.into_iter().map(Some).collect::<Vec<_>>();
+        for (rule, matches) in rules.iter().enumerate() {
+            for new in &mut added {
+                let Some(instr) = *new else {
+                    continue;
+                };
+                let Some(old) = removed
+                    .iter_mut()
+                    .find(|old| old.is_some_and(|old| matches(old, instr)))
+                else {
+                    continue;
+                };
+                if rule >= 2 {
+                    self.renamed += 1;
+                }
+                if instr.op() == Some(Op::Id) && old.unwrap().op() != Some(Op::Id) {
+                    self.id += 1;
+                }
+                *old = None;
+                *new = None;
+            }
+        }
+        self.removed += removed.iter().flatten().count();
+        self.added += added.iter().flatten().count();
+    }
+}
+
+fn instructions(function: &Function) -> Vec<&Instruction> {
+    function
+        .instrs
+        .iter()
+        .filter(|instr| instr.label().is_none())
+        .collect()
+}
+
+/// The stats of every pass on every function, in the order they ran.
+pub struct Stats {
+    pub functions: Vec<(String, Vec<PassStats>)>,
+}
+
+impl fmt::Display for Stats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (name, passes) in &self.functions {
+            writeln!(f, "@{name}:")?;
+            let width = passes
+                .iter()
+                .map(|stats| stats.pass.len())
+                .chain(["pass".len()])
+                .max()
+                .unwrap();
+            writeln!(
+                f,
+                "  {:width$}  before  after  id  renamed  removed  added",
+                "pass"
+            )?;
+            for stats in passes {
+                writeln!(
+                    f,
+                    "  {:width$}  {:>6}  {:>5}  {:>2}  {:>7}  {:>7}  {:>5}",
+                    stats.pass,
+                    stats.before,
+                    stats.after,
+                    stats.id,
+                    stats.renamed,
+                    stats.removed,
+                    stats.added
+                )?;
+            }
+            for diff in passes.iter().filter_map(|stats| stats.diff.as_ref()) {
+                write!(f, "{diff}")?;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Edit {
+    Keep(usize, usize),
+    Remove(usize),
+    Add(usize),
+}
+
+/// A shortest edit script from `old` to `new` by longest common subsequence, with the removals of
+/// each change before its additions.
+fn edits<T: PartialEq>(old: &[T], new: &[T]) -> Vec<Edit> {
+    let prefix = old.iter().zip(new).take_while(|(a, b)| a == b).count();
+    let suffix = old[prefix..]
+        .iter()
+        .rev()
+        .zip(new[prefix..].iter().rev())
+        .take_while(|(a, b)| a == b)
+        .count();
+    let (a, b) = (
+        &old[prefix..old.len() - suffix],
+        &new[prefix..new.len() - suffix],
+    );
+
+    // lcs[i][j] is the length of the longest common subsequence of a[i..] and b[j..].
+    let mut lcs = vec![vec![0u32; b.len() + 1]; a.len() + 1];
+    for i in (0..a.len()).rev() {
+        for j in (0..b.len()).rev() {
+            lcs[i][j] = if a[i] == b[j] {
+                lcs[i + 1][j + 1] + 1
+            } else {
+                lcs[i + 1][j].max(lcs[i][j + 1])
+            };
+        }
+    }
+
+    let mut edits = (0..prefix).map(|i| Edit::Keep(i, i)).collect::<Vec<_>>();
+    let (mut i, mut j) = (0, 0);
+    let mut added = Vec::new();
+    while i < a.len() || j < b.len() {
+        if i < a.len() && j < b.len() && a[i] == b[j] {
+            edits.append(&mut added);
+            edits.push(Edit::Keep(prefix + i, prefix + j));
+            i += 1;
+            j += 1;
+        } else if j == b.len() || (i < a.len() && lcs[i + 1][j] >= lcs[i][j + 1]) {
+            edits.push(Edit::Remove(prefix + i));
+            i += 1;
+        } else {
+            added.push(Edit::Add(prefix + j));
+            j += 1;
+        }
+    }
+    edits.append(&mut added);
+    edits.extend((0..suffix).map(|k| Edit::Keep(old.len() - suffix + k, new.len() - suffix + k)));
+    edits
+}
+
+const CONTEXT: usize = 3;
+
+/// `old` and `new` as a unified diff with three lines of context, empty when they are the same.
+fn unified(old_name: &str, new_name: &str, old: &[&str], new: &[&str]) -> String {
+    let edits = edits(old, new);
+    let changes = edits
+        .iter()
+        .enumerate()
+        .filter(|(_, edit)| !matches!(edit, Edit::Keep(..)))
+        .map(|(k, _)| k)
+        .collect::<Vec<_>>();
+    if changes.is_empty() {
+        return String::new();
+    }
+
+    let mut output = format!("--- {old_name}\n+++ {new_name}\n");
+    let mut k = 0;
+    while k < changes.len() {
+        // Changes closer than twice the context share a hunk.
+        let mut last = k;
+        while last + 1 < changes.len() && changes[last + 1] - changes[last] <= 2 * CONTEXT + 1 {
+            last += 1;
+        }
+        let start = changes[k].saturating_sub(CONTEXT);
+        let end = (changes[last] + CONTEXT + 1).min(edits.len());
+        let hunk = &edits[start..end];
+
+        // Where the hunk starts in both texts, counting the lines before it.
+        let (mut old_start, mut new_start) = (0, 0);
+        for edit in &edits[..start] {
+            match edit {
+                Edit::Keep(..) => {
+                    old_start += 1;
+                    new_start += 1;
+                }
+                Edit::Remove(_) => old_start += 1,
+                Edit::Add(_) => new_start += 1,
+            }
+        }
+        let old_len = hunk
+            .iter()
+            .filter(|edit| !matches!(edit, Edit::Add(_)))
+            .count();
+        let new_len = hunk
+            .iter()
+            .filter(|edit| !matches!(edit, Edit::Remove(_)))
+            .count();
+        output.push_str(&format!(
+            "@@ -{} +{} @@\n",
+            range(old_start, old_len),
+            range(new_start, new_len)
+        ));
+        for edit in hunk {
+            match *edit {
+                Edit::Keep(a, _) => output.push_str(&format!(" {}\n", old[a])),
+                Edit::Remove(a) => output.push_str(&format!("-{}\n", old[a])),
+                Edit::Add(b) => output.push_str(&format!("+{}\n", new[b])),
+            }
+        }
+        k = last + 1;
+    }
+    output
+}
+
+// A hunk's range as `diff -u` prints it: an empty range names the line before it.
+fn range(start: usize, len: usize) -> String {
+    match len {
+        0 => format!("{start},0"),
+        1 => format!("{}", start + 1),
+        _ => format!("{},{len}", start + 1),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{unified, PassStats};
+    use crate::parser::parse;
+
+    #[test]
+    fn test_pass_stats() {
+        let before = "@main {\n  a: int = const 1;\n  b: int = const 1;\n  a: int = add a b;\n  c: int = const 2;\n  print a b;\n}\n";
+        let after = "@main {\n  a_prime: int = const 1;\n  b: int = id a_prime;\n  a: int = add a_prime b;\n  print a b;\n  ret;\n}\n";
+        let before = &parse(before).unwrap().functions[0];
+        let after = &parse(after).unwrap().functions[0];
+
+        let stats = PassStats::new("lvn", before, after, false);
+        assert_eq!(
+            (
+                stats.before,
+                stats.after,
+                stats.id,
+                stats.renamed,
+                stats.removed,
+                stats.added
+            ),
+            (5, 5, 1, 1, 1, 1)
+        );
+        assert_eq!(stats.diff, None);
+
+        let same = PassStats::new("tdce", after, after, true);
+        assert_eq!(
+            (same.id, same.renamed, same.removed, same.added),
+            (0, 0, 0, 0)
+        );
+        assert_eq!(same.diff.as_deref(), Some(""));
+    }
+
+    #[test]
+    fn test_unified() {
+        let old = ["a", "b", "c", "d", "e", "f", "g", "h", "i", "j", "k"];
+        let new = ["a", "B", "c", "d", "e", "f", "g", "h", "i", "j", "k", "l"];
+        assert_eq!(
+            unified("old", "new", &old, &new),
+            "\
+--- old
++++ new
+@@ -1,5 +1,5 @@
+ a
+-b
++B
+ c
+ d
+ e
+@@ -9,3 +9,4 @@
+ i
+ j
+ k
++l
+"
+        );
+        assert_eq!(
+            unified("old", "new", &["a"], &[]),
+            "--- old\n+++ new\n@@ -1 +0,0 @@\n-a\n"
+        );
+    }
+}
@@ -0,0 +1,458 @@
+use std::{
+    collections::{BTreeSet, HashMap, HashSet},
+    fmt,
+};
+
+use crate::{
+    basic_block::{partition, BasicBlock},
+    error::Location,
+    ir::{Bril, Function, Instruction, Op, Position},
+    ssa::Cfg,
+};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct VerifyError {
+    pub function: String,
+    /// Index into `Function::instrs` of the offending instruction, `None` for the argument list.
+    pub index: Option<usize>,
+    pub pos: Option<Position>,
+    pub message: String,
+}
+
+impl fmt::Display for VerifyError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let at = Location {
+            function: &self.function,
+            index: self.index,
+            pos: self.pos,
+        };
+        write!(f, "{at}: {}", self.message)
+    }
+}
+
+/// Checks the control flow structure of every function: labels are unique and defined,
+/// terminators name the right number of labels, no unlabeled code follows a `br` or `jmp`, a function
+/// returning a value ends in a terminator, phis lead their blocks and agree with the block's
+/// predecessors, and every variable used is defined somewhere.
+pub fn verify(bril: &Bril) -> Vec<VerifyError> {
+    run(bril, false)
+}
+
+/// Like [`verify`], and also checks that every variable is defined once and that each
+/// definition dominates its uses.
+pub fn verify_ssa(bril: &Bril) -> Vec<VerifyError> {
+    run(bril, true)
+}
+
+fn run(bril: &Bril, ssa: bool) -> Vec<VerifyError> {
+    let mut errors = Vec::new();
+    for function in &bril.functions {
+        let mut verifier = Verifier {
+            function,
+            errors: &mut errors,
+        };
+        verifier.verify(ssa);
+    }
+    errors
+}
+
+// A basic block as `BasicBlock::new_blocks` labels it, with the indices of its instructions in the function.
+struct Block {
+    label: String,
+    indices: Vec<usize>,
+}
+
+struct Verifier<'a, 'e> {
+    function: &'a Function,
+    errors: &'e mut Vec<VerifyError>,
+}
+
+impl<'a, 'e> Verifier<'a, 'e> {
+    fn error(&mut self, index: Option<usize>, message: String) {
+        let pos = index.and_then(|index| self.function.instrs[index].pos());
+        self.errors.push(VerifyError {
+            function: self.function.name.clone(),
+            index,
+            pos,
+            message,
+        });
+    }
+
+    fn verify(&mut self, ssa: bool) {
+        let labels_ok = self.labels();
+        self.terminators();
+        self.uses();
+
+        // Building the CFG needs every jump target to exist exactly once.
+        if !labels_ok || self.function.instrs.is_empty() {
+            return;
+        }
+
+        let blocks = self.blocks();
+        let Ok(cfg) = Cfg::new(self.function) else {
+            return;
+        };
+        self.phis(&blocks, &cfg);
+
+        if ssa {
+            self.single_definition();
+            self.dominance(&blocks, &cfg);
+        }
+    }
+
+    fn labels(&mut self) -> bool {
+        let instrs = &self.function.instrs;
+        let mut ok = true;
+
+        let mut defined = HashSet::new();
+        for (index, instr) in instrs.iter().enumerate() {
+            if let Some(label) = instr.label() {
+                if !defined.insert(label) {
+                    self.error(Some(index), format!("multiply defined label .{label}"));
+                    ok = false;
+                }
+            }
+        }
+
+        for (index, instr) in instrs.iter().enumerate() {
+            for label in instr.labels() {
+                if !defined.contains(label.as_str()) {
+                    self.error(Some(index), format!("label .{label} undefined"));
+                    ok = false;
+                }
+            }
+        }
+
+        ok
+    }
+
+    // The CFG takes its edges from the labels of the terminators and guards.
+    fn terminators(&mut self) {
+        for (index, instr) in self.function.instrs.iter().enumerate() {
+            let expected = match instr.op() {
+                Some(Op::Jmp | Op::Guard) => 1,
+                Some(Op::Br) => 2,
+                Some(Op::Ret) => 0,
+                _ => continue,
+            };
+            let found = instr.labels().len();
+            if found != expected {
+                let op = instr.op().unwrap();
+                self.error(
+                    Some(index),
+                    format!("{op} needs {expected} labels; found {found}"),
+                );
+            }
+        }
+
+        // Nothing can jump to a block that `partition` starts after a `br` or `jmp` unless it is
+        // labeled.
+        let blocks = partition(&self.function.instrs);
+        let mut start = 0;
+        for pair in blocks.windows(2) {
+            start += pair[0].len();
+            if let Some(op @ (Op::Br | Op::Jmp)) = pair[0].last().unwrap().op() {
+                if pair[1][0].label().is_none() {
+                    let message = format!("unlabeled instruction after {op} is unreachable");
+                    self.error(Some(start), message);
+                }
+            }
+        }
+
+        // A function returning a value mustn't fall off its end.
+        let instrs = &self.function.instrs;
+        if let Some(r#type) = &self.function.r#type {
+            let last = instrs.last().and_then(Instruction::op);
+            if !matches!(last, Some(Op::Br | Op::Jmp | Op::Ret)) {
+                let message = format!("missing ret at the end of a function returning {}", r#type);
+                self.error(instrs.len().checked_sub(1), message);
+            }
+        }
+    }
+
+    // Phi arguments may name variables that are undefined along that edge, so only other uses count.
+    fn uses(&mut self) {
+        let function = self.function;
+        let defined: HashSet<&str> = function
+            .args
+            .iter()
+            .flatten()
+            .map(|arg| arg.name.as_str())
+            .chain(function.instrs.iter().filter_map(Instruction::dest))
+            .collect();
+
+        for (index, instr) in function.instrs.iter().enumerate() {
+            if instr.op() == Some(Op::Phi) {
+                continue;
+            }
+            for arg in instr.args() {
+                if !defined.contains(arg.as_str()) {
+                    self.error(Some(index), format!("{arg} is used but never defined"));
+                }
+            }
+        }
+    }
+
+    fn blocks(&self) -> Vec<Block> {
+        let instrs = &self.function.instrs;
+        let mut start = 0;
+
+        // `new_blocks` only adds a leading label and a trailing terminator to each block of `partition`.
+        partition(instrs)
+            .iter()
+            .zip(BasicBlock::new_blocks(instrs))
+            .map(|(original, block)| {
+                let indices = (start..start + original.len()).collect();
+                start += original.len();
+                Block {
+                    label: block[0].label().unwrap().to_string(),
+                    indices,
+                }
+            })
+            .collect()
+    }
+
+    fn phis(&mut self, blocks: &[Block], cfg: &Cfg) {
+        let instrs = &self.function.instrs;
+
+        for block in blocks {
+            let predecessors = cfg.predecessors(&block.label);
+            let mut leading = true;
+
+            for &index in &block.indices {
+                let Instruction::Value {
+                    op: Op::Phi,
+                    dest,
+                    labels,
+                    ..
+                } = &instrs[index]
+                else {
+                    leading &= instrs[index].label().is_some();
+                    continue;
+                };
+
+                if !leading {
+                    let message = format!("phi for {dest} follows a non-phi instruction");
+                    self.error(Some(index), message);
+                }
+                for label in labels {
+                    if !predecessors.contains(label) {
+                        let message = format!(
+                            "phi for {dest} names .{label}, which is not a predecessor of .{}",
+                            block.label
+                        );
+                        self.error(Some(index), message);
+                    }
+                }
+                for predecessor in predecessors.iter().collect::<BTreeSet<_>>() {
+                    if !labels.contains(predecessor) {
+                        let message =
+                            format!("phi for {dest} has no value for predecessor .{predecessor}");
+                        self.error(Some(index), message);
+                    }
+                }
+            }
+        }
+    }
+
+    fn single_definition(&mut self) {
+        let function = self.function;
+        let mut defined = HashSet::new();
+
+        for arg in function.args.iter().flatten() {
+            if !defined.insert(arg.name.as_str()) {
+                self.error(None, format!("{} is defined more than once", arg.name));
+            }
+        }
+        for (index, instr) in function.instrs.iter().enumerate() {
+            if let Some(dest) = instr.dest() {
+                if !defined.insert(dest) {
+                    self.error(Some(index), format!("{dest} is defined more than once"));
+                }
+            }
+        }
+    }
+
+    fn dominance(&mut self, blocks: &[Block], cfg: &Cfg) {
+        let instrs = &self.function.instrs;
+        let dominators = cfg.dominators();
+
+        // Where each variable is first defined, as (block, index); arguments dominate everything.
+        let mut definitions: HashMap<&str, Option<(&str, usize)>> = HashMap::new();
+        for arg in self.function.args.iter().flatten() {
+            definitions.entry(&arg.name).or_insert(None);
+        }
+        for block in blocks {
+            for &index in &block.indices {
+                if let Some(dest) = instrs[index].dest() {
+                    definitions
+                        .entry(dest)
+                        .or_insert(Some((&block.label, index)));
+                }
+            }
+        }
+
+        let dominates = |a: &str, b: &str| dominators.get(b).is_none_or(|doms| doms.contains(a));
+
+        for block in blocks {
+            // Uses in unreachable blocks are never executed.
+            if !dominators.contains_key(block.label.as_str()) {
+                continue;
+            }
+
+            for &index in &block.indices {
+                let instr = &instrs[index];
+
+                if let Instruction::Value {
+                    op: Op::Phi,
+                    args,
+                    labels,
+                    ..
+                } = instr
+                {
+                    for (arg, label) in args.iter().zip(labels) {
+                        if let Some(Some((def_block, def_index))) = definitions.get(arg.as_str()) {
+                            // Phis run in order, so one may read an earlier phi of the same block.
+                            let earlier = *def_block == block.label && *def_index < index;
+                            if !earlier && !dominates(def_block, label) {
+                                let message = format!(
+                                    "definition of {arg} in .{def_block} does not dominate predecessor .{label}"
+                                );
+                                self.error(Some(index), message);
+                            }
+                        }
+                    }
+                    continue;
+                }
+
+                // Each variable once, however many times the instruction reads it.
+                let mut seen = HashSet::new();
+                for arg in instr.args().iter().filter(|arg| seen.insert(*arg)) {
+                    let Some(Some((def_block, def_index))) = definitions.get(arg.as_str()) else {
+                        continue;
+                    };
+                    let message = if *def_block == block.label {
+                        (*def_index >= index)
+                            .then(|| format!("{arg} is used before its definition"))
+                    } else {
+                        (!dominates(def_block, &block.label)).then(|| {
+                            format!(
+                                "definition of {arg} in .{def_block} does not dominate its use in .{}",
+                                block.label
+                            )
+                        })
+                    };
+                    if let Some(message) = message {
+                        self.error(Some(index), message);
+                    }
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use insta::glob;
+
+    use super::{verify, verify_ssa};
+    use crate::parser::parse;
+
+    #[test]
+    fn test_verify_corpus() {
+        glob!(
+            "..",
+            "tests/{examples,test/interp,test/print}/**/*.bril",
+            |path| {
+                let bril = parse(&std::fs::read_to_string(path).unwrap()).unwrap();
+                let errors = verify(&bril);
+                // These leave code after a `jmp` that never runs on purpose.
+                if ["tdce/skipped.bril", "core/jmp.bril"]
+                    .iter()
+                    .any(|dead| path.ends_with(dead))
+                {
+                    let messages = errors
+                        .iter()
+                        .map(|e| e.message.as_str())
+                        .collect::<Vec<_>>();
+                    assert_eq!(messages, ["unlabeled instruction after jmp is unreachable"]);
+                    return;
+                }
+                assert!(errors.is_empty(), "{}: {errors:?}", path.display());
+            }
+        );
+    }
+
+    #[test]
+    fn test_verify_ssa_corpus() {
+        glob!("..", "tests/examples/to_ssa/*.out", |path| {
+            let bril = parse(&std::fs::read_to_string(path).unwrap()).unwrap();
+            let errors = verify_ssa(&bril);
+            assert!(errors.is_empty(), "{}: {errors:?}", path.display());
+        });
+    }
+
+    #[test]
+    fn test_verify_errors() {
+        let cases = [
+            (
+                "@main {\n.a:\n  jmp .b;\n.a:\n  ret;\n}\n",
+                vec!["@main[2] 4:1: multiply defined label .a", "@main[1] 3:3: label .b undefined"],
+            ),
+            (
+                "@main {\n  jmp;\n  print x;\n.end:\n  ret .end;\n}\n",
+                vec![
+                    "@main[0] 2:3: jmp needs 1 labels; found 0",
+                    "@main[3] 5:3: ret needs 0 labels; found 1",
+                    "@main[1] 3:3: unlabeled instruction after jmp is unreachable",
+                    "@main[1] 3:3: x is used but never defined",
+                ],
+            ),
+            (
+                "@main(c: bool) {\n  br c .l .r;\n  nop;\n.l:\n  jmp .r;\n  nop;\n.r:\n  ret;\n}\n",
+                vec![
+                    "@main[1] 3:3: unlabeled instruction after br is unreachable",
+                    "@main[4] 6:3: unlabeled instruction after jmp is unreachable",
+                ],
+            ),
+            (
+                "@f(c: bool): int {\n  one: int = const 1;\n  br c .yes .no;\n.yes:\n  ret one;\n.no:\n  print one;\n}\n@g: int {\n}\n",
+                vec![
+                    "@f[5] 7:3: missing ret at the end of a function returning int",
+                    "@g: missing ret at the end of a function returning int",
+                ],
+            ),
+            (
+                "@main(c: bool) {\n.entry:\n  br c .l .r;\n.l:\n  x: int = const 1;\n  jmp .m;\n.r:\n  jmp .m;\n.m:\n  print x;\n  y: int = phi x .entry;\n}\n",
+                vec![
+                    "@main[9] 11:3: phi for y follows a non-phi instruction",
+                    "@main[9] 11:3: phi for y names .entry, which is not a predecessor of .m",
+                    "@main[9] 11:3: phi for y has no value for predecessor .l",
+                    "@main[9] 11:3: phi for y has no value for predecessor .r",
+                ],
+            ),
+        ];
+
+        for (src, expected) in cases {
+            let errors = verify(&parse(src).unwrap());
+            let errors = errors.iter().map(ToString::to_string).collect::<Vec<_>>();
+            assert_eq!(errors, expected);
+        }
+    }
+
+    #[test]
+    fn test_verify_ssa_errors() {
+        let src = "@main(c: bool) {\n.entry:\n  br c .l .r;\n.l:\n  x: int = const 1;\n  jmp .m;\n.r:\n  x: int = const 2;\n  jmp .m;\n.m:\n  print x;\n  y: int = add z z;\n  z: int = const 3;\n}\n";
+        let errors = verify_ssa(&parse(src).unwrap());
+        let errors = errors.iter().map(ToString::to_string).collect::<Vec<_>>();
+
+        assert_eq!(
+            errors,
+            [
+                "@main[6] 8:3: x is defined more than once",
+                "@main[9] 11:3: definition of x in .l does not dominate its use in .m",
+                "@main[10] 12:3: z is used before its definition",
+            ]
+        );
+    }
+}